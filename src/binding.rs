@@ -0,0 +1,274 @@
+use anyhow::{Result, anyhow};
+use evdev_rs::enums::{EV_KEY, int_to_ev_key};
+
+// 按键组合中的一个按键名称，pos为其在配置字符串中的位置（从1开始的字符序号）
+pub struct Token {
+    pub name: String,
+    pub pos: usize,
+}
+
+// 把形如"ctrl+shift+z"的按键组合拆分为按键名称；名称统一转换为小写
+//
+// 多个按键组合之间以','分隔（用于宏），例如"ctrl+a, ctrl+c"；
+// '+'与','出现在需要按键名称的位置时表示加号键与逗号键本身，因此"ctrl++"表示ctrl与加号键的组合，"ctrl+,"表示ctrl与逗号键的组合
+pub fn tokenize(value: &str) -> Result<Vec<Vec<Token>>> {
+    enum State {
+        Separator, // 刚遇到分隔符（或处于开头），需要按键名称
+        Name,      // 正在读取按键名称
+        Done,      // 按键名称已结束，需要分隔符
+    }
+    let mut steps: Vec<Vec<Token>> = vec![vec![]];
+    let mut state = State::Separator;
+    let mut separator = '+';
+    for (i, c) in value.chars().enumerate() {
+        let pos = i + 1;
+        let tokens = steps.last_mut().unwrap();
+        state = match (state, c) {
+            (State::Separator, c) if c.is_whitespace() => State::Separator,
+            (State::Separator, c) => {
+                tokens.push(Token {
+                    name: c.to_lowercase().to_string(),
+                    pos,
+                });
+                // 单独的'+'或','不会与后面的字符组成名称
+                match c {
+                    '+' | ',' => State::Done,
+                    _ => State::Name,
+                }
+            }
+            (State::Name | State::Done, '+' | ',') => {
+                if c == ',' {
+                    steps.push(vec![]);
+                }
+                separator = c;
+                State::Separator
+            }
+            (State::Name, c) if c.is_whitespace() => State::Done,
+            (State::Name, c) => {
+                if let Some(token) = tokens.last_mut() {
+                    token.name.extend(c.to_lowercase());
+                }
+                State::Name
+            }
+            (State::Done, c) if c.is_whitespace() => State::Done,
+            (State::Done, c) => {
+                return Err(anyhow!("第{}个字符'{}'之前缺少'+'", pos, c));
+            }
+        };
+    }
+    match state {
+        State::Separator if steps.len() == 1 && steps[0].is_empty() => Err(anyhow!("按键组合为空")),
+        State::Separator => Err(anyhow!("末尾的'{}'之后缺少按键", separator)),
+        _ => Ok(steps),
+    }
+}
+
+// 根据小写的按键名称查找按键码：先查找常用名称与别名，再查找evdev的按键名称（可省略"KEY_"前缀），最后尝试解析为数字形式的按键码
+pub fn key_code(name: &str) -> Option<EV_KEY> {
+    if let Some(code) = named_key(name) {
+        return Some(code);
+    }
+    let code = match name.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => name.parse::<u32>().ok(),
+    };
+    if let Some(code) = code {
+        return int_to_ev_key(code);
+    }
+    let upper = name.to_uppercase();
+    upper
+        .parse::<EV_KEY>()
+        .or_else(|_| format!("KEY_{}", upper).parse::<EV_KEY>())
+        .ok()
+}
+
+#[rustfmt::skip]
+fn named_key(name: &str) -> Option<EV_KEY> {
+    let code = match name {
+        // Letters
+        "a" => EV_KEY::KEY_A, "b" => EV_KEY::KEY_B, "c" => EV_KEY::KEY_C, "d" => EV_KEY::KEY_D,
+        "e" => EV_KEY::KEY_E, "f" => EV_KEY::KEY_F, "g" => EV_KEY::KEY_G, "h" => EV_KEY::KEY_H,
+        "i" => EV_KEY::KEY_I, "j" => EV_KEY::KEY_J, "k" => EV_KEY::KEY_K, "l" => EV_KEY::KEY_L,
+        "m" => EV_KEY::KEY_M, "n" => EV_KEY::KEY_N, "o" => EV_KEY::KEY_O, "p" => EV_KEY::KEY_P,
+        "q" => EV_KEY::KEY_Q, "r" => EV_KEY::KEY_R, "s" => EV_KEY::KEY_S, "t" => EV_KEY::KEY_T,
+        "u" => EV_KEY::KEY_U, "v" => EV_KEY::KEY_V, "w" => EV_KEY::KEY_W, "x" => EV_KEY::KEY_X,
+        "y" => EV_KEY::KEY_Y, "z" => EV_KEY::KEY_Z,
+        // Numbers
+        "0" => EV_KEY::KEY_0, "1" => EV_KEY::KEY_1, "2" => EV_KEY::KEY_2, "3" => EV_KEY::KEY_3,
+        "4" => EV_KEY::KEY_4, "5" => EV_KEY::KEY_5, "6" => EV_KEY::KEY_6, "7" => EV_KEY::KEY_7,
+        "8" => EV_KEY::KEY_8, "9" => EV_KEY::KEY_9,
+        // Symbols
+        "-" => EV_KEY::KEY_MINUS, "=" => EV_KEY::KEY_EQUAL, "\\" => EV_KEY::KEY_BACKSLASH,
+        "`" => EV_KEY::KEY_GRAVE, "[" => EV_KEY::KEY_LEFTBRACE, "]" => EV_KEY::KEY_RIGHTBRACE,
+        ";" => EV_KEY::KEY_SEMICOLON, "'" => EV_KEY::KEY_APOSTROPHE, "," => EV_KEY::KEY_COMMA,
+        "." => EV_KEY::KEY_DOT, "/" => EV_KEY::KEY_SLASH,
+        // 主键盘上的加号与等号共用一个按键，需要同时按住shift才能输入加号；小键盘的加号键为"kpplus"
+        "+" | "plus" => EV_KEY::KEY_EQUAL,
+        // Special keys
+        "esc" | "escape" => EV_KEY::KEY_ESC, "tab" => EV_KEY::KEY_TAB,
+        "backspace" | "bksp" => EV_KEY::KEY_BACKSPACE, "enter" | "return" => EV_KEY::KEY_ENTER,
+        "space" | "spacebar" => EV_KEY::KEY_SPACE, "home" => EV_KEY::KEY_HOME, "end" => EV_KEY::KEY_END,
+        "pageup" | "pgup" => EV_KEY::KEY_PAGEUP, "pagedown" | "pgdn" => EV_KEY::KEY_PAGEDOWN,
+        "insert" | "ins" => EV_KEY::KEY_INSERT, "delete" | "del" => EV_KEY::KEY_DELETE,
+        "printscreen" | "prtsc" => EV_KEY::KEY_PRINT, "capslock" | "caps" => EV_KEY::KEY_CAPSLOCK,
+        "menu" => EV_KEY::KEY_COMPOSE,
+        // Modifier keys
+        "ctrl" | "control" | "lctrl" => EV_KEY::KEY_LEFTCTRL,
+        "shift" | "lshift" => EV_KEY::KEY_LEFTSHIFT,
+        "alt" | "option" | "opt" | "lalt" => EV_KEY::KEY_LEFTALT,
+        "meta" | "super" | "win" | "cmd" | "command" | "lmeta" => EV_KEY::KEY_LEFTMETA,
+        "rctrl" => EV_KEY::KEY_RIGHTCTRL, "rshift" => EV_KEY::KEY_RIGHTSHIFT,
+        "ralt" | "altgr" => EV_KEY::KEY_RIGHTALT, "rmeta" => EV_KEY::KEY_RIGHTMETA,
+        _ => return None,
+    };
+    Some(code)
+}
+
+// 只有KEY_*按键码属于键盘；BTN_*是鼠标、手柄等设备的按钮，启用后虚拟键盘可能不再被识别为键盘
+pub fn is_keyboard_key(code: EV_KEY) -> bool {
+    format!("{:?}", code).starts_with("KEY_")
+}
+
+pub fn is_modifier(code: EV_KEY) -> bool {
+    matches!(
+        code,
+        EV_KEY::KEY_LEFTCTRL
+            | EV_KEY::KEY_RIGHTCTRL
+            | EV_KEY::KEY_LEFTSHIFT
+            | EV_KEY::KEY_RIGHTSHIFT
+            | EV_KEY::KEY_LEFTALT
+            | EV_KEY::KEY_RIGHTALT
+            | EV_KEY::KEY_LEFTMETA
+            | EV_KEY::KEY_RIGHTMETA
+    )
+}
+
+// 多媒体与系统按键；桌面环境期望由声明了这些按键的设备上报，因此不放在虚拟键盘上
+pub fn is_consumer_key(code: EV_KEY) -> bool {
+    matches!(
+        code,
+        EV_KEY::KEY_MUTE
+            | EV_KEY::KEY_VOLUMEDOWN
+            | EV_KEY::KEY_VOLUMEUP
+            | EV_KEY::KEY_MICMUTE
+            | EV_KEY::KEY_PLAYPAUSE
+            | EV_KEY::KEY_PLAY
+            | EV_KEY::KEY_PAUSE
+            | EV_KEY::KEY_STOPCD
+            | EV_KEY::KEY_NEXTSONG
+            | EV_KEY::KEY_PREVIOUSSONG
+            | EV_KEY::KEY_FASTFORWARD
+            | EV_KEY::KEY_REWIND
+            | EV_KEY::KEY_RECORD
+            | EV_KEY::KEY_EJECTCD
+            | EV_KEY::KEY_MEDIA
+            | EV_KEY::KEY_BRIGHTNESSDOWN
+            | EV_KEY::KEY_BRIGHTNESSUP
+            | EV_KEY::KEY_KBDILLUMTOGGLE
+            | EV_KEY::KEY_KBDILLUMDOWN
+            | EV_KEY::KEY_KBDILLUMUP
+            | EV_KEY::KEY_SWITCHVIDEOMODE
+            | EV_KEY::KEY_CALC
+            | EV_KEY::KEY_MAIL
+            | EV_KEY::KEY_WWW
+            | EV_KEY::KEY_HOMEPAGE
+            | EV_KEY::KEY_SEARCH
+            | EV_KEY::KEY_BOOKMARKS
+            | EV_KEY::KEY_BACK
+            | EV_KEY::KEY_FORWARD
+            | EV_KEY::KEY_REFRESH
+            | EV_KEY::KEY_COMPUTER
+            | EV_KEY::KEY_CONFIG
+            | EV_KEY::KEY_POWER
+            | EV_KEY::KEY_SLEEP
+            | EV_KEY::KEY_WAKEUP
+            | EV_KEY::KEY_SUSPEND
+            | EV_KEY::KEY_COFFEE
+            | EV_KEY::KEY_WLAN
+            | EV_KEY::KEY_BLUETOOTH
+            | EV_KEY::KEY_RFKILL
+            | EV_KEY::KEY_TOUCHPAD_TOGGLE
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(value: &str) -> Vec<Vec<String>> {
+        tokenize(value)
+            .unwrap()
+            .into_iter()
+            .map(|tokens| tokens.into_iter().map(|token| token.name).collect())
+            .collect()
+    }
+
+    #[test]
+    fn tokenize_combos() {
+        assert_eq!(names("Ctrl+Shift+Z"), [["ctrl", "shift", "z"]]);
+        assert_eq!(names(" ctrl + alt "), [["ctrl", "alt"]]);
+        assert_eq!(
+            names("ctrl+a, ctrl+c"),
+            [vec!["ctrl", "a"], vec!["ctrl", "c"]]
+        );
+    }
+
+    #[test]
+    fn tokenize_literal_separators() {
+        assert_eq!(names("ctrl++"), [["ctrl", "+"]]);
+        assert_eq!(names("ctrl+,"), [["ctrl", ","]]);
+        assert_eq!(names("+"), [["+"]]);
+        assert_eq!(names(",, a"), [vec![","], vec!["a"]]);
+    }
+
+    #[test]
+    fn tokenize_positions() {
+        let steps = tokenize("ctrl+ z").unwrap();
+        assert_eq!(steps[0][0].pos, 1);
+        assert_eq!(steps[0][1].pos, 7);
+    }
+
+    #[test]
+    fn tokenize_errors() {
+        assert!(tokenize("").is_err());
+        assert!(tokenize("  ").is_err());
+        assert!(tokenize("ctrl+").is_err());
+        assert!(tokenize("ctrl+a,").is_err());
+        assert!(tokenize("ctrl a").is_err());
+    }
+
+    #[test]
+    fn key_code_names() {
+        assert_eq!(key_code("a"), Some(EV_KEY::KEY_A));
+        assert_eq!(key_code("control"), Some(EV_KEY::KEY_LEFTCTRL));
+        assert_eq!(key_code("altgr"), Some(EV_KEY::KEY_RIGHTALT));
+        assert_eq!(key_code("+"), Some(EV_KEY::KEY_EQUAL));
+        assert_eq!(key_code("plus"), Some(EV_KEY::KEY_EQUAL));
+        assert_eq!(key_code("kpplus"), Some(EV_KEY::KEY_KPPLUS));
+        assert_eq!(key_code("key_volumeup"), Some(EV_KEY::KEY_VOLUMEUP));
+        assert_eq!(key_code("f13"), Some(EV_KEY::KEY_F13));
+        assert_eq!(key_code("nosuchkey"), None);
+    }
+
+    #[test]
+    fn key_code_numbers() {
+        assert_eq!(key_code("30"), Some(EV_KEY::KEY_A));
+        assert_eq!(key_code("0x1e"), Some(EV_KEY::KEY_A));
+        assert_eq!(key_code("0xzz"), None);
+    }
+
+    #[test]
+    fn keyboard_keys() {
+        assert!(is_keyboard_key(EV_KEY::KEY_A));
+        assert!(!is_keyboard_key(EV_KEY::BTN_LEFT));
+        assert!(!is_keyboard_key(key_code("btn_left").unwrap()));
+    }
+
+    #[test]
+    fn consumer_keys() {
+        assert!(is_consumer_key(EV_KEY::KEY_VOLUMEUP));
+        assert!(is_consumer_key(EV_KEY::KEY_PLAYPAUSE));
+        assert!(!is_consumer_key(EV_KEY::KEY_A));
+        assert!(!is_consumer_key(EV_KEY::KEY_LEFTCTRL));
+    }
+}
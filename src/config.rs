@@ -1,3 +1,5 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::os::unix::fs::MetadataExt;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -11,16 +13,22 @@ use nix::sys::eventfd::{EfdFlags, EventFd};
 use nix::sys::inotify::{self, Inotify, InotifyEvent};
 use serde::Deserialize;
 
+use crate::binding;
 use crate::cancel::CancelToken;
+use crate::display;
 use crate::error;
+use crate::typing;
 use crate::warn;
 
 macro_rules! try_into {
-    ($value: ident => $($field:ident),+ $(,)?) => {
+    ($value: ident => $($field:ident),+ $(,)? $(; $($other:ident: $other_value:expr),+ $(,)?)?) => {
         Ok(Self {
             $(
-                $field: $value.$field.try_into().context(concat!("转换字段'", stringify!($field), "'时发生错误"))?
-            ),+
+                $field: $value.$field.try_into().context(concat!("转换字段'", stringify!($field), "'时发生错误"))?,
+            )+
+            $($(
+                $other: $other_value,
+            )+)?
         })
     };
 }
@@ -34,30 +42,261 @@ struct RawConfig {
     // Y轴最大值
     y_max_value: Option<u16>,
 
+    // 虚拟绘图板坐标范围相对于设备坐标范围的放大倍数
+    axis_scale: Option<u16>,
+
     // X轴的比例映射
     x_map: Option<(f32, f32)>,
 
     // Y轴的比例映射
     y_map: Option<(f32, f32)>,
 
+    // 映射到指定显示输出（连接器名称）
+    map_output: Option<String>,
+
+    // 各个显示输出左上角在桌面中的位置（像素），以连接器名称为键
+    output_layout: Option<display::OutputLayout>,
+
+    // 精确模式下绘图板映射到的区域占屏幕的比例
+    precision_mode_scale: Option<f32>,
+
+    // 笔尖坐标的防抖滤波器
+    filter: Option<RawFilterConfig>,
+
+    // 笔画稳定器
+    stabilizer: Option<RawStabilizerConfig>,
+
+    // 画笔模式
+    pen_mode: Option<PenMode>,
+
+    // 相对模式（鼠标模式）的参数
+    relative_mode: Option<RawRelativeModeConfig>,
+
+    // 虚拟绘图板的设备类型
+    digitizer_type: Option<DigitizerType>,
+
+    // 保持原有功能的笔侧键在作为鼠标使用时对应的鼠标按键
+    mouse_buttons: Option<RawMouseButtonsConfig>,
+
+    // 按住笔侧键滚动的参数
+    scroll: Option<RawScrollConfig>,
+
+    // type:输入文本时，非ASCII字符使用的Unicode输入方式
+    unicode_input: Option<RawUnicodeInputConfig>,
+
+    // exec运行命令的参数
+    exec: Option<RawExecConfig>,
+
+    // DRM的sysfs目录
+    drm_sysfs_root: Option<PathBuf>,
+
+    // 可通过cycleArea切换的映射区域
+    #[serde(default, rename = "area")]
+    areas: Vec<RawAreaConfig>,
+
     // 按键映射配置方案
     #[serde(rename = "keymap")]
     keymaps: Vec<RawKeymapConfig>,
+
+    // 依次按下多个按钮触发的按键映射，对所有方案生效
+    #[serde(default, rename = "sequence")]
+    sequences: Vec<RawSequenceConfig>,
+}
+#[derive(Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    deny_unknown_fields
+)]
+enum RawFilterConfig {
+    MovingAverage {
+        // 参与平均的坐标个数
+        window: Option<usize>,
+    },
+    Exponential {
+        // 新坐标所占的权重
+        alpha: Option<f32>,
+    },
+    OneEuro {
+        // 最小截止频率（Hz）
+        min_cutoff: Option<f32>,
+
+        // 截止频率随速度增长的系数
+        beta: Option<f32>,
+
+        // 计算速度时使用的截止频率（Hz）
+        d_cutoff: Option<f32>,
+    },
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawStabilizerConfig {
+    // 虚拟绳子的长度（设备坐标）
+    length: Option<f32>,
+
+    // 是否默认启用
+    enabled: Option<bool>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawRelativeModeConfig {
+    // 指针移动速度：每个设备坐标对应的指针移动量
+    speed: Option<f32>,
+
+    // 指针加速度：移动速度越快，指针移动量放大得越多
+    acceleration: Option<f32>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawScrollConfig {
+    // 滚动速度：每个设备坐标对应的高精度滚动量（120为滚轮的一格）
+    speed: Option<f32>,
+
+    // 是否反转水平/垂直滚动方向
+    invert_x: Option<bool>,
+    invert_y: Option<bool>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawExecConfig {
+    // 运行命令的用户与用户组，不能为root
+    uid: u32,
+    gid: u32,
+
+    // 命令的环境变量，例如DISPLAY、WAYLAND_DISPLAY、XDG_RUNTIME_DIR
+    env: Option<BTreeMap<String, String>>,
+
+    // 超时时间（毫秒），超时后结束命令的进程组；为0时不限制
+    timeout: Option<u64>,
+
+    // 允许运行的程序列表文件，每行一个绝对路径；必须属于root且其他用户不可写，只能进一步限制可以运行的程序
+    allowlist: Option<PathBuf>,
+}
+#[derive(Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    deny_unknown_fields
+)]
+enum RawUnicodeInputConfig {
+    // 按下start后输入十六进制码位，再按下end确认，例如GTK/IBus的ctrl+shift+u
+    Hex {
+        start: Option<String>,
+        end: Option<String>,
+    },
+    // 不支持输入非ASCII字符
+    None,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawMouseButtonsConfig {
+    stylus_button0: Option<MouseButton>,
+    stylus_button1: Option<MouseButton>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawAreaConfig {
+    // 映射区域名称
+    name: String,
+
+    // X轴的比例映射
+    x_map: Option<(f32, f32)>,
+
+    // Y轴的比例映射
+    y_map: Option<(f32, f32)>,
+
+    // 映射到指定显示输出（连接器名称）
+    output: Option<String>,
 }
 #[derive(Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 struct RawKeymapConfig {
-    button0: String,
-    button1: String,
-    button2: String,
-    button3: String,
-    button4: String,
-    button5: String,
-    button6: String,
-    button7: String,
-    ring0: String,
-    ring1: String,
-    ring_button: String,
+    button0: RawBinding,
+    button1: RawBinding,
+    button2: RawBinding,
+    button3: RawBinding,
+    button4: RawBinding,
+    button5: RawBinding,
+    button6: RawBinding,
+    button7: RawBinding,
+    ring0: RawBinding,
+    ring1: RawBinding,
+    ring_button: RawBinding,
+    stylus_button0: RawBinding,
+    stylus_button1: RawBinding,
+    ring_acceleration: Option<RawRingAccelerationConfig>,
+}
+// 按键映射可以写作字符串（例如"ctrl+shift+z"）或按键名称的数组（例如["ctrl", "shift", "z"]），也可以写作带有更多选项的表
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawBinding {
+    Keys(RawKeys),
+    Table(RawBindingTable),
+    Gesture(RawGestureTable),
+}
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawKeys {
+    String(String),
+    Array(Vec<String>),
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawBindingTable {
+    keys: Option<RawKeys>,
+
+    // 宏的步骤，不能与keys同时配置
+    #[serde(rename = "macro")]
+    macro_steps: Option<Vec<String>>,
+
+    // 要运行的程序及其参数，不能与keys、macro同时配置
+    exec: Option<Vec<String>>,
+
+    // 按键组合至少保持按下的时间（毫秒）
+    hold_time: Option<u64>,
+
+    // 修饰键与其他按键之间的间隔（毫秒）
+    delay: Option<u64>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawSequenceConfig {
+    // 依次按下的按钮：button0至button7或ringButton
+    keys: Vec<String>,
+
+    // 按完序列后触发的按键映射
+    action: RawBinding,
+
+    // 相邻两次按下的最大间隔（毫秒）
+    timeout: Option<u64>,
+}
+// 按键面板的按钮按短按、长按与双击分别映射
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawGestureTable {
+    tap: Option<Box<RawBinding>>,
+    hold: Option<Box<RawBinding>>,
+    double_tap: Option<Box<RawBinding>>,
+
+    // 按下超过该时间（毫秒）视为长按
+    hold_threshold: Option<u64>,
+
+    // 松开后在该时间（毫秒）内再次按下视为双击
+    double_tap_interval: Option<u64>,
+}
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawRingAccelerationConfig {
+    // 相邻两格转动的间隔小于该值（毫秒）时开始加速
+    threshold: Option<u64>,
+
+    // 加速系数：转动得越快，倍数增长得越多
+    factor: Option<f32>,
+
+    // 最大倍数
+    max: Option<u32>,
 }
 impl Default for RawKeymapConfig {
     fn default() -> Self {
@@ -65,100 +304,392 @@ impl Default for RawKeymapConfig {
             ($($field:ident),+ $(,)?) => {
                 Self {
                     $(
-                        $field: "fallback".to_string(),
+                        $field: RawBinding::Keys(RawKeys::String("fallback".to_string())),
                     )+
+                    ring_acceleration: None,
                 }
             };
         }
         default_fallback! {
             button0, button1, button2, button3, button4, button5, button6, button7,
-            ring0, ring1, ring_button,
+            ring0, ring1, ring_button, stylus_button0, stylus_button1,
         }
     }
 }
 
+#[derive(Clone)]
+struct ImmediateGesture {
+    tap: ImmediateKeymap,
+    hold: ImmediateKeymap,
+    double_tap: ImmediateKeymap,
+    hold_threshold: Duration,
+    double_tap_interval: Duration,
+}
+impl ImmediateGesture {
+    const DEFAULT_HOLD_THRESHOLD: u64 = 300;
+    const DEFAULT_DOUBLE_TAP_INTERVAL: u64 = 250;
+}
 #[derive(Clone)]
 enum ImmediateKeymap {
     None,
-    Press(Arc<Vec<EV_KEY>>),
+    Press(Arc<KeyCombo>),
+    Macro(Arc<Vec<MacroStep>>),
+    Type(Arc<str>), // 加载配置时转换为Macro
+    Exec(Arc<Vec<String>>),
+    Gesture(Box<ImmediateGesture>),
     SwitchSchema,
+    CycleArea,
+    PrecisionMode,
+    TogglePrecisionMode,
+    ToggleStabilizer,
+    TogglePenMode,
+    Passthrough,
+    Scroll,
+    Wheel(Wheel),
+    Latch(Arc<Vec<EV_KEY>>),  // 按下按键组合，在下一次按键或笔画结束后释放
+    Toggle(Arc<Vec<EV_KEY>>), // 按下按键组合，再次触发时释放
+    Click(MouseButton),       // 鼠标按键跟随物理按键按下与松开
+    DoubleClick(MouseButton), // 按下时双击鼠标按键
+    Pad,
     Fallback,
 }
-impl TryFrom<String> for ImmediateKeymap {
-    type Error = Error;
-    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
-        let iter = value.split("+").map(|s| s.trim());
-        let mut parts = Vec::new();
-        for part in iter {
-            if !parts.contains(&part) {
-                parts.push(part);
-            }
+impl ImmediateKeymap {
+    // 不能和其他键组合的特殊行为；名称不区分大小写
+    fn special(name: &str) -> Option<Self> {
+        match name {
+            "switchschema" => Some(Self::SwitchSchema),
+            "cyclearea" => Some(Self::CycleArea),
+            "precisionmode" => Some(Self::PrecisionMode),
+            "toggleprecisionmode" => Some(Self::TogglePrecisionMode),
+            "togglestabilizer" => Some(Self::ToggleStabilizer),
+            "togglepenmode" => Some(Self::TogglePenMode),
+            "passthrough" => Some(Self::Passthrough),
+            "scroll" => Some(Self::Scroll),
+            "pad" => Some(Self::Pad),
+            "fallback" => Some(Self::Fallback),
+            "none" => Some(Self::None),
+            _ => None,
         }
-        if parts.contains(&"switchSchema") {
-            if parts.len() > 1 {
-                return Err(anyhow!("不能把'switchSchema'和其他键组合"));
+    }
+
+    // 滚轮配置形如"scroll:<方向>[:<格数>]"，格数可以为小数，默认为1
+    fn wheel(value: &str) -> Result<Self> {
+        let mut args = value.split(':').map(|s| s.trim());
+        let (horizontal, sign) = match args.next() {
+            Some("up") => (false, 1f32),
+            Some("down") => (false, -1f32),
+            Some("right") => (true, 1f32),
+            Some("left") => (true, -1f32),
+            _ => {
+                return Err(anyhow!(
+                    "'scroll:{}'的方向必须为up、down、left或right",
+                    value
+                ));
             }
-            return Ok(ImmediateKeymap::SwitchSchema);
+        };
+        let steps = match args.next() {
+            Some(steps) => steps
+                .parse::<f32>()
+                .ok()
+                .filter(|x| *x > 0f32)
+                .with_context(|| format!("'scroll:{}'的格数必须为大于0的数字", value))?,
+            None => 1f32,
+        };
+        if args.next().is_some() {
+            return Err(anyhow!("'scroll:{}'不是有效的滚轮配置", value));
+        }
+        let amount = (sign * steps * Wheel::HI_RES_PER_STEP as f32).round() as i32;
+        if amount == 0 {
+            return Err(anyhow!("'scroll:{}'的格数过小", value));
         }
-        if parts.contains(&"fallback") {
-            if parts.len() > 1 {
-                return Err(anyhow!("不能把'fallback'和其他键组合"));
+        Ok(Self::Wheel(Wheel { horizontal, amount }))
+    }
+}
+impl TryFrom<RawBinding> for ImmediateKeymap {
+    type Error = Error;
+    fn try_from(value: RawBinding) -> std::result::Result<Self, Self::Error> {
+        match value {
+            RawBinding::Keys(keys) => keys.try_into(),
+            RawBinding::Table(table) => {
+                let keys = match (table.keys, table.macro_steps, table.exec) {
+                    (Some(keys), None, None) => keys,
+                    (None, Some(steps), None) => {
+                        if table.hold_time.is_some() || table.delay.is_some() {
+                            return Err(anyhow!("holdTime与delay不能用于宏"));
+                        }
+                        return Self::macro_from_strings(&steps);
+                    }
+                    (None, None, Some(argv)) => {
+                        if table.hold_time.is_some() || table.delay.is_some() {
+                            return Err(anyhow!("holdTime与delay不能用于exec"));
+                        }
+                        return Self::exec(argv);
+                    }
+                    _ => return Err(anyhow!("keys、macro与exec必须配置且只能配置其中一个")),
+                };
+                let keymap = Self::try_from(keys)?;
+                let mut combo = match keymap {
+                    Self::Press(combo) => combo.as_ref().clone(),
+                    _ => return Err(anyhow!("holdTime与delay只能用于按键组合")),
+                };
+                combo.hold = Duration::from_millis(table.hold_time.unwrap_or(0));
+                combo.delay = Duration::from_millis(table.delay.unwrap_or(0));
+                Ok(Self::Press(Arc::new(combo)))
+            }
+            RawBinding::Gesture(table) => {
+                let mut gesture = ImmediateGesture {
+                    tap: Self::None,
+                    hold: Self::None,
+                    double_tap: Self::None,
+                    hold_threshold: Duration::from_millis(
+                        table
+                            .hold_threshold
+                            .unwrap_or(ImmediateGesture::DEFAULT_HOLD_THRESHOLD),
+                    ),
+                    double_tap_interval: Duration::from_millis(
+                        table
+                            .double_tap_interval
+                            .unwrap_or(ImmediateGesture::DEFAULT_DOUBLE_TAP_INTERVAL),
+                    ),
+                };
+                for (name, raw, keymap) in [
+                    ("tap", table.tap, &mut gesture.tap),
+                    ("hold", table.hold, &mut gesture.hold),
+                    ("doubleTap", table.double_tap, &mut gesture.double_tap),
+                ] {
+                    let Some(raw) = raw else {
+                        continue;
+                    };
+                    *keymap = Self::try_from(*raw).with_context(|| format!("{}无效", name))?;
+                    if let Self::Passthrough
+                    | Self::Scroll
+                    | Self::Pad
+                    | Self::Fallback
+                    | Self::Gesture(_) = keymap
+                    {
+                        return Err(anyhow!(
+                            "{}不能为passthrough、scroll、pad、fallback或者嵌套的tap/hold/doubleTap",
+                            name
+                        ));
+                    }
+                }
+                if let (Self::None, Self::None, Self::None) =
+                    (&gesture.tap, &gesture.hold, &gesture.double_tap)
+                {
+                    return Err(anyhow!("tap、hold与doubleTap至少需要配置一个"));
+                }
+                if gesture.hold_threshold.is_zero() || gesture.double_tap_interval.is_zero() {
+                    return Err(anyhow!("holdThreshold与doubleTapInterval必须大于0"));
+                }
+                Ok(Self::Gesture(Box::new(gesture)))
             }
-            return Ok(ImmediateKeymap::Fallback);
         }
-        if parts.contains(&"none") {
-            if parts.len() > 1 {
-                return Err(anyhow!("不能把'none'和其他键组合"));
+    }
+}
+impl TryFrom<RawKeys> for ImmediateKeymap {
+    type Error = Error;
+    fn try_from(value: RawKeys) -> std::result::Result<Self, Self::Error> {
+        match value {
+            RawKeys::String(value) => {
+                let lower = value.trim().to_lowercase();
+                if let Some(args) = lower.strip_prefix("scroll:") {
+                    return Self::wheel(args);
+                }
+                if let Some(combo) = lower.strip_prefix("latch:") {
+                    return Ok(Self::Latch(Arc::new(combo_codes(combo.trim())?)));
+                }
+                if let Some(combo) = lower.strip_prefix("toggle:") {
+                    return Ok(Self::Toggle(Arc::new(combo_codes(combo.trim())?)));
+                }
+                if let Some(name) = lower.strip_prefix("doubleclick:") {
+                    return Ok(Self::DoubleClick(MouseButton::from_name(name.trim())?));
+                }
+                // click:与button:的鼠标按键都跟随物理按键按下与松开
+                if let Some(name) = lower
+                    .strip_prefix("click:")
+                    .or_else(|| lower.strip_prefix("button:"))
+                {
+                    return Ok(Self::Click(MouseButton::from_name(name.trim())?));
+                }
+                // 命令以空白分隔程序与参数，不经过shell解析
+                if lower.starts_with("exec:") {
+                    let argv = value.trim()["exec:".len()..]
+                        .split_whitespace()
+                        .map(|x| x.to_string())
+                        .collect();
+                    return Self::exec(argv);
+                }
+                // 文本区分大小写，不能使用转换成小写后的值
+                if lower.starts_with("type:") {
+                    let text = value.trim()["type:".len()..].trim();
+                    let text = match text.strip_prefix('"').and_then(|x| x.strip_suffix('"')) {
+                        Some(text) => text,
+                        None => text,
+                    };
+                    return Ok(Self::Type(Arc::from(text)));
+                }
+                let mut steps = binding::tokenize(&value)
+                    .with_context(|| format!("无法解析按键映射配置'{}'", value))?;
+                // 以','分隔的多个按键组合为宏
+                if steps.len() > 1 {
+                    return Self::macro_from_strings(&[value]);
+                }
+                Self::from_tokens(steps.remove(0), |token| {
+                    format!("'{}'中第{}个字符处的'{}'", value, token.pos, token.name)
+                })
             }
-            return Ok(ImmediateKeymap::None);
-        }
-
-        let mut codes = Vec::with_capacity(parts.len());
-        macro_rules! match_key {
-            ($value:ident, { $($key:literal => $code:expr),+ $(,)? }) => {
-                match $value {
-                    $(x if x == $key => {
-                        codes.push($code);
-                    }),+
-                    _ => {
-                        return Err(anyhow!("'{}'不是有效的按键映射配置", $value));
+            RawKeys::Array(values) => {
+                let mut tokens = Vec::with_capacity(values.len());
+                for (i, value) in values.iter().enumerate() {
+                    let name = value.trim().to_lowercase();
+                    if name.is_empty() {
+                        return Err(anyhow!("按键映射数组中第{}个元素为空", i + 1));
                     }
+                    tokens.push(binding::Token { name, pos: i + 1 });
                 }
+                if tokens.is_empty() {
+                    return Err(anyhow!("按键映射数组为空"));
+                }
+                Self::from_tokens(tokens, |token| {
+                    format!("按键映射数组中第{}个元素'{}'", token.pos, token.name)
+                })
+            }
+        }
+    }
+}
+impl ImmediateKeymap {
+    fn from_tokens<F>(tokens: Vec<binding::Token>, describe: F) -> Result<Self>
+    where
+        F: Fn(&binding::Token) -> String,
+    {
+        for token in &tokens {
+            if let Some(keymap) = Self::special(&token.name) {
+                if tokens.len() > 1 {
+                    return Err(anyhow!("{}：不能和其他键组合", describe(token)));
+                }
+                return Ok(keymap);
+            }
+        }
+        Ok(ImmediateKeymap::Press(Arc::new(KeyCombo {
+            codes: key_codes(&tokens, &describe)?,
+            hold: Duration::ZERO,
+            delay: Duration::ZERO,
+        })))
+    }
+
+    fn exec(argv: Vec<String>) -> Result<Self> {
+        let program = argv.first().context("exec缺少要运行的程序")?;
+        if !Path::new(program).is_absolute() {
+            return Err(anyhow!("exec的程序'{}'必须是绝对路径", program));
+        }
+        Ok(Self::Exec(Arc::new(argv)))
+    }
+
+    // 宏的每个步骤可以是按键组合，或者"delay:<毫秒>"、"down:<按键>"、"up:<按键>"
+    fn macro_from_strings(values: &[String]) -> Result<Self> {
+        let mut steps = vec![];
+        for value in values {
+            let describe = |token: &binding::Token| {
+                format!("'{}'中第{}个字符处的'{}'", value, token.pos, token.name)
             };
+            let tokenized =
+                binding::tokenize(value).with_context(|| format!("无法解析宏的步骤'{}'", value))?;
+            for mut tokens in tokenized {
+                let first = &tokens[0];
+                if let Some(ms) = first.name.strip_prefix("delay:") {
+                    if tokens.len() > 1 {
+                        return Err(anyhow!("{}：不能和其他键组合", describe(&tokens[1])));
+                    }
+                    let ms = ms
+                        .trim()
+                        .parse::<u64>()
+                        .with_context(|| format!("{}：延迟必须为毫秒数", describe(first)))?;
+                    steps.push(MacroStep::Delay(Duration::from_millis(ms)));
+                    continue;
+                }
+                let (prefix, down) = match first.name.split_once(':') {
+                    Some(("down", _)) => ("down:", Some(true)),
+                    Some(("up", _)) => ("up:", Some(false)),
+                    _ => ("", None),
+                };
+                let rest = tokens[0].name[prefix.len()..].trim().to_string();
+                if rest.is_empty() {
+                    return Err(anyhow!("{}：缺少按键", describe(&tokens[0])));
+                }
+                tokens[0].name = rest;
+                for token in &tokens {
+                    if Self::special(&token.name).is_some() {
+                        return Err(anyhow!("{}：不能用于宏", describe(token)));
+                    }
+                }
+                let codes = key_codes(&tokens, describe)?;
+                steps.push(match down {
+                    Some(true) => MacroStep::Down(codes),
+                    Some(false) => MacroStep::Up(codes),
+                    None => MacroStep::Combo(codes),
+                });
+            }
         }
-        for part in parts {
-            match_key!(part, {
-                // Letters
-                "a" => EV_KEY::KEY_A, "b" => EV_KEY::KEY_B, "c" => EV_KEY::KEY_C, "d" => EV_KEY::KEY_D,
-                "e" => EV_KEY::KEY_E, "f" => EV_KEY::KEY_F, "g" => EV_KEY::KEY_G, "h" => EV_KEY::KEY_H,
-                "i" => EV_KEY::KEY_I, "j" => EV_KEY::KEY_J, "k" => EV_KEY::KEY_K, "l" => EV_KEY::KEY_L,
-                "m" => EV_KEY::KEY_M, "n" => EV_KEY::KEY_N, "o" => EV_KEY::KEY_O, "p" => EV_KEY::KEY_P,
-                "q" => EV_KEY::KEY_Q, "r" => EV_KEY::KEY_R, "s" => EV_KEY::KEY_S, "t" => EV_KEY::KEY_T,
-                "u" => EV_KEY::KEY_U, "v" => EV_KEY::KEY_V, "w" => EV_KEY::KEY_W, "x" => EV_KEY::KEY_X,
-                "y" => EV_KEY::KEY_Y, "z" => EV_KEY::KEY_Z,
-                // Numbers
-                "0" => EV_KEY::KEY_0, "1" => EV_KEY::KEY_1, "2" => EV_KEY::KEY_2, "3" => EV_KEY::KEY_3,
-                "4" => EV_KEY::KEY_4, "5" => EV_KEY::KEY_5, "6" => EV_KEY::KEY_6, "7" => EV_KEY::KEY_7,
-                "8" => EV_KEY::KEY_8, "9" => EV_KEY::KEY_9,
-                // Symbols
-                "-" => EV_KEY::KEY_MINUS, "=" => EV_KEY::KEY_EQUAL, "\\" => EV_KEY::KEY_BACKSLASH,
-                "`" => EV_KEY::KEY_GRAVE, "[" => EV_KEY::KEY_LEFTBRACE, "]" => EV_KEY::KEY_RIGHTBRACE,
-                ";" => EV_KEY::KEY_SEMICOLON, "'" => EV_KEY::KEY_APOSTROPHE, "," => EV_KEY::KEY_COMMA,
-                "." => EV_KEY::KEY_DOT, "/" => EV_KEY::KEY_SLASH,
-                // Special keys
-                "esc" => EV_KEY::KEY_ESC, "tab" => EV_KEY::KEY_TAB, "backspace" => EV_KEY::KEY_BACKSPACE,
-                "enter" => EV_KEY::KEY_ENTER, "space" => EV_KEY::KEY_SPACE, "home" => EV_KEY::KEY_HOME,
-                "end" => EV_KEY::KEY_END, "pageup" => EV_KEY::KEY_PAGEUP, "pagedown" => EV_KEY::KEY_PAGEDOWN,
-                "insert" => EV_KEY::KEY_INSERT, "delete" => EV_KEY::KEY_DELETE,
-                // Modifier keys
-                "ctrl" => EV_KEY::KEY_LEFTCTRL, "shift" => EV_KEY::KEY_LEFTSHIFT,
-                "alt" => EV_KEY::KEY_LEFTALT, "meta" => EV_KEY::KEY_LEFTMETA,
-            });
+        if steps.is_empty() {
+            return Err(anyhow!("宏没有任何步骤"));
+        }
+        Ok(Self::Macro(Arc::new(steps)))
+    }
+}
+
+impl ImmediateKeymap {
+    fn expand_text(&mut self, unicode_input: &UnicodeInputConfig) -> Result<()> {
+        match self {
+            Self::Type(text) => {
+                let steps = typing::text_steps(text, unicode_input)
+                    .with_context(|| format!("无法输入文本'{}'", text))?;
+                *self = Self::Macro(Arc::new(steps));
+            }
+            Self::Gesture(gesture) => {
+                gesture.tap.expand_text(unicode_input)?;
+                gesture.hold.expand_text(unicode_input)?;
+                gesture.double_tap.expand_text(unicode_input)?;
+            }
+            _ => {}
         }
-        Ok(ImmediateKeymap::Press(Arc::new(codes)))
+        Ok(())
     }
 }
 
+// 解析单个按键组合
+fn combo_codes(value: &str) -> Result<Vec<EV_KEY>> {
+    let mut steps = binding::tokenize(value)?;
+    if steps.len() > 1 {
+        return Err(anyhow!("'{}'只能是一个按键组合", value));
+    }
+    key_codes(&steps.remove(0), |token| {
+        format!("'{}'中第{}个字符处的'{}'", value, token.pos, token.name)
+    })
+}
+
+fn key_codes<F>(tokens: &[binding::Token], describe: F) -> Result<Vec<EV_KEY>>
+where
+    F: Fn(&binding::Token) -> String,
+{
+    let mut codes = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        let code = binding::key_code(&token.name)
+            .with_context(|| format!("{}：不是有效的按键", describe(token)))?;
+        if !binding::is_keyboard_key(code) {
+            return Err(anyhow!(
+                "{}：{:?}不是键盘按键，鼠标按键请使用click:或button:",
+                describe(token),
+                code
+            ));
+        }
+        if codes.contains(&code) {
+            return Err(anyhow!("{}：按键重复", describe(token)));
+        }
+        codes.push(code);
+    }
+    Ok(codes)
+}
+
 struct ImmediateKeymapConfig {
     button0: ImmediateKeymap,
     button1: ImmediateKeymap,
@@ -171,13 +702,39 @@ struct ImmediateKeymapConfig {
     ring0: ImmediateKeymap,
     ring1: ImmediateKeymap,
     ring_button: ImmediateKeymap,
+    stylus_button0: ImmediateKeymap,
+    stylus_button1: ImmediateKeymap,
+    ring_acceleration: Option<RingAccelerationConfig>, // 为None时使用前一个配置方案的设置
 }
 impl TryFrom<RawKeymapConfig> for ImmediateKeymapConfig {
     type Error = anyhow::Error;
     fn try_from(value: RawKeymapConfig) -> Result<Self> {
+        let ring_acceleration = match value.ring_acceleration {
+            Some(raw) => {
+                let mut conf = RingAccelerationConfig::default();
+                conf.threshold = raw
+                    .threshold
+                    .map(Duration::from_millis)
+                    .unwrap_or(conf.threshold);
+                conf.factor = raw.factor.unwrap_or(conf.factor);
+                conf.max = raw.max.unwrap_or(conf.max);
+                if conf.threshold.is_zero() {
+                    return Err(anyhow!("ringAcceleration.threshold必须大于0"));
+                }
+                if conf.factor < 0f32 {
+                    return Err(anyhow!("ringAcceleration.factor不能小于0"));
+                }
+                if conf.max < 1 {
+                    return Err(anyhow!("ringAcceleration.max必须大于等于1"));
+                }
+                Some(conf)
+            }
+            None => None,
+        };
         try_into! { value =>
             button0, button1, button2, button3, button4, button5, button6, button7,
-            ring0, ring1, ring_button,
+            ring0, ring1, ring_button, stylus_button0, stylus_button1;
+            ring_acceleration: ring_acceleration,
         }
     }
 }
@@ -194,8 +751,27 @@ impl ImmediateKeymapConfig {
         }
         resolve! {
             button0, button1, button2, button3, button4, button5, button6, button7,
-            ring0, ring1, ring_button,
+            ring0, ring1, ring_button, stylus_button0, stylus_button1,
+        }
+        if self.ring_acceleration.is_none() {
+            self.ring_acceleration = other.ring_acceleration;
+        }
+    }
+
+    // 把type:要输入的文本转换为宏
+    fn expand_text(&mut self, unicode_input: &UnicodeInputConfig) -> Result<()> {
+        macro_rules! expand_text {
+            ($($field:ident),+ $(,)?) => {
+                $(
+                    self.$field.expand_text(unicode_input).context(stringify!($field))?;
+                )+
+            };
+        }
+        expand_text! {
+            button0, button1, button2, button3, button4, button5, button6, button7,
+            ring0, ring1, ring_button, stylus_button0, stylus_button1,
         }
+        Ok(())
     }
 }
 
@@ -203,10 +779,208 @@ impl ImmediateKeymapConfig {
 pub struct Config {
     pub x_max_value: u16,
     pub y_max_value: u16,
+    pub axis_scale: i32,
     pub x_map: Option<(f32, f32)>,
     pub y_map: Option<(f32, f32)>,
+    pub areas: Vec<AreaConfig>,
+    pub precision_mode_scale: f32,
+    pub filter: Option<FilterConfig>,
+    pub stabilizer: StabilizerConfig,
+    pub pen_mode: PenMode,
+    pub relative_mode: RelativeModeConfig,
+    pub digitizer_type: DigitizerType,
+    pub mouse_buttons: MouseButtonsConfig,
+    pub scroll: ScrollConfig,
+    pub exec: Option<ExecConfig>,
+    pub sequences: Vec<SequenceConfig>,
+    pub drm_sysfs_root: Option<PathBuf>,
     pub keymaps: Vec<KeymapConfig>,
 }
+#[derive(Clone, Copy)]
+pub enum FilterConfig {
+    MovingAverage {
+        window: usize,
+    },
+    Exponential {
+        alpha: f32,
+    },
+    OneEuro {
+        min_cutoff: f32,
+        beta: f32,
+        d_cutoff: f32,
+    },
+}
+impl TryFrom<RawFilterConfig> for FilterConfig {
+    type Error = Error;
+    fn try_from(value: RawFilterConfig) -> Result<Self> {
+        match value {
+            RawFilterConfig::MovingAverage { window } => {
+                let window = window.unwrap_or(4);
+                if !(1..=64).contains(&window) {
+                    return Err(anyhow!("window必须在1到64之间"));
+                }
+                Ok(Self::MovingAverage { window })
+            }
+            RawFilterConfig::Exponential { alpha } => {
+                let alpha = alpha.unwrap_or(0.5);
+                if alpha <= 0f32 || alpha > 1f32 {
+                    return Err(anyhow!("alpha必须大于0且不大于1"));
+                }
+                Ok(Self::Exponential { alpha })
+            }
+            RawFilterConfig::OneEuro {
+                min_cutoff,
+                beta,
+                d_cutoff,
+            } => {
+                let min_cutoff = min_cutoff.unwrap_or(1.0);
+                let beta = beta.unwrap_or(0.001);
+                let d_cutoff = d_cutoff.unwrap_or(1.0);
+                if min_cutoff <= 0f32 {
+                    return Err(anyhow!("minCutoff必须大于0"));
+                }
+                if beta < 0f32 {
+                    return Err(anyhow!("beta不能小于0"));
+                }
+                if d_cutoff <= 0f32 {
+                    return Err(anyhow!("dCutoff必须大于0"));
+                }
+                Ok(Self::OneEuro {
+                    min_cutoff,
+                    beta,
+                    d_cutoff,
+                })
+            }
+        }
+    }
+}
+#[derive(Clone, Copy)]
+pub struct StabilizerConfig {
+    pub length: f32,
+    pub enabled: bool,
+}
+impl Default for StabilizerConfig {
+    fn default() -> Self {
+        Self {
+            length: 200.0,
+            enabled: false,
+        }
+    }
+}
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PenMode {
+    // 画笔作为绘图板使用，上报绝对坐标
+    #[default]
+    Absolute,
+
+    // 画笔作为鼠标使用，上报相对位移
+    Relative,
+}
+#[derive(Clone, Copy)]
+pub struct RelativeModeConfig {
+    pub speed: f32,
+    pub acceleration: f32,
+}
+impl Default for RelativeModeConfig {
+    fn default() -> Self {
+        Self {
+            speed: 0.1,
+            acceleration: 0.0,
+        }
+    }
+}
+#[derive(Clone, Copy)]
+pub struct ScrollConfig {
+    pub speed: f32,
+    pub invert_x: bool,
+    pub invert_y: bool,
+}
+impl Default for ScrollConfig {
+    fn default() -> Self {
+        Self {
+            speed: 0.5,
+            invert_x: false,
+            invert_y: false,
+        }
+    }
+}
+#[derive(Clone)]
+pub struct ExecConfig {
+    pub uid: u32,
+    pub gid: u32,
+    pub env: BTreeMap<String, String>,
+    pub timeout: Option<Duration>,
+}
+impl ExecConfig {
+    const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+    const DEFAULT_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+}
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DigitizerType {
+    // 数位笔设备，上报BTN_TOOL_PEN、压力与倾斜
+    #[default]
+    Pen,
+
+    // 绝对坐标的鼠标设备，供不支持绘图板的程序使用
+    Mouse,
+}
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    #[serde(alias = "back")]
+    Side,
+    #[serde(alias = "forward")]
+    Extra,
+}
+impl MouseButton {
+    fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "left" => Ok(Self::Left),
+            "right" => Ok(Self::Right),
+            "middle" => Ok(Self::Middle),
+            "side" | "back" => Ok(Self::Side),
+            "extra" | "forward" => Ok(Self::Extra),
+            _ => Err(anyhow!(
+                "'{}'不是有效的鼠标按键，必须为left、right、middle、back（side）或forward（extra）",
+                name
+            )),
+        }
+    }
+
+    pub fn code(self) -> EV_KEY {
+        match self {
+            Self::Left => EV_KEY::BTN_LEFT,
+            Self::Right => EV_KEY::BTN_RIGHT,
+            Self::Middle => EV_KEY::BTN_MIDDLE,
+            Self::Side => EV_KEY::BTN_SIDE,
+            Self::Extra => EV_KEY::BTN_EXTRA,
+        }
+    }
+}
+#[derive(Clone, Copy)]
+pub struct MouseButtonsConfig {
+    pub stylus_button0: MouseButton,
+    pub stylus_button1: MouseButton,
+}
+impl Default for MouseButtonsConfig {
+    fn default() -> Self {
+        Self {
+            stylus_button0: MouseButton::Right,
+            stylus_button1: MouseButton::Middle,
+        }
+    }
+}
+#[derive(Clone, Default)]
+pub struct AreaConfig {
+    pub name: String,
+    pub x_map: Option<(f32, f32)>,
+    pub y_map: Option<(f32, f32)>,
+}
 #[derive(Clone, Default)]
 pub struct KeymapConfig {
     pub button0: Keymap,
@@ -220,20 +994,205 @@ pub struct KeymapConfig {
     pub ring0: Keymap,
     pub ring1: Keymap,
     pub ring_button: Keymap,
+    pub stylus_button0: Keymap,
+    pub stylus_button1: Keymap,
+    pub ring_acceleration: Option<RingAccelerationConfig>,
+}
+// 转环加速：快速转动时重复触发按键映射，或者放大滚轮的滚动量
+#[derive(Clone, Copy)]
+pub struct RingAccelerationConfig {
+    pub threshold: Duration,
+    pub factor: f32,
+    pub max: u32,
+}
+impl Default for RingAccelerationConfig {
+    fn default() -> Self {
+        Self {
+            threshold: Duration::from_millis(100),
+            factor: 1.0,
+            max: 4,
+        }
+    }
+}
+impl RingAccelerationConfig {
+    // 根据相邻两格转动的间隔计算倍数：间隔为阈值的1/n时，倍数为1+factor*(n-1)
+    pub fn multiplier(&self, interval: Duration) -> f32 {
+        if interval >= self.threshold {
+            return 1f32;
+        }
+        let ratio = self.threshold.as_secs_f32() / interval.as_secs_f32().max(0.001);
+        (1f32 + self.factor * (ratio - 1f32)).min(self.max as f32)
+    }
 }
 #[derive(Clone, Default)]
 pub enum Keymap {
     #[default]
     None,
-    Press(Arc<Vec<EV_KEY>>),
+    Press(Arc<KeyCombo>),
+    Macro(Arc<Vec<MacroStep>>),
+    Exec(Arc<Vec<String>>), // 要运行的程序及其参数
+    Gesture(Arc<Gesture>),  // 按短按、长按与双击分别映射
     SwitchSchema,
+    CycleArea,
+    PrecisionMode,
+    TogglePrecisionMode,
+    ToggleStabilizer,
+    TogglePenMode,
+    Passthrough,
+    Scroll,
+    Wheel(Wheel),
+    Latch(Arc<Vec<EV_KEY>>),  // 按下按键组合，在下一次按键或笔画结束后释放
+    Toggle(Arc<Vec<EV_KEY>>), // 按下按键组合，再次触发时释放
+    Click(MouseButton),       // 鼠标按键跟随物理按键按下与松开
+    DoubleClick(MouseButton), // 按下时双击鼠标按键
+    Pad,
+}
+// 按键组合；修饰键先于其他按键按下，并晚于其他按键释放
+#[derive(Clone)]
+pub struct KeyCombo {
+    pub codes: Vec<EV_KEY>,
+    pub hold: Duration,  // 至少保持按下的时间
+    pub delay: Duration, // 修饰键与其他按键之间的间隔
+}
+// 输入非ASCII字符的方式
+pub enum UnicodeInputConfig {
+    Hex {
+        start: Vec<EV_KEY>,
+        end: Vec<EV_KEY>,
+    },
+    None,
+}
+impl TryFrom<RawUnicodeInputConfig> for UnicodeInputConfig {
+    type Error = Error;
+    fn try_from(value: RawUnicodeInputConfig) -> Result<Self> {
+        match value {
+            RawUnicodeInputConfig::Hex { start, end } => {
+                let start = match start {
+                    Some(start) => combo_codes(&start).context("start无效")?,
+                    None => vec![EV_KEY::KEY_LEFTCTRL, EV_KEY::KEY_LEFTSHIFT, EV_KEY::KEY_U],
+                };
+                let end = match end {
+                    Some(end) => combo_codes(&end).context("end无效")?,
+                    None => vec![EV_KEY::KEY_SPACE],
+                };
+                Ok(Self::Hex { start, end })
+            }
+            RawUnicodeInputConfig::None => Ok(Self::None),
+        }
+    }
+}
+// 依次按下多个按钮时触发的按键映射
+#[derive(Clone)]
+pub struct SequenceConfig {
+    pub buttons: Vec<EV_KEY>, // 按钮对应的虚拟按键面板按键码
+    pub action: Keymap,
+    pub timeout: Duration,
+}
+impl SequenceConfig {
+    const DEFAULT_TIMEOUT: u64 = 800;
+
+    fn new(raw: RawSequenceConfig, unicode_input: &UnicodeInputConfig) -> Result<Self> {
+        if raw.keys.len() < 2 {
+            return Err(anyhow!("keys至少需要两个按钮"));
+        }
+        let mut buttons = Vec::with_capacity(raw.keys.len());
+        for key in &raw.keys {
+            let code = match key.as_str() {
+                "button0" => EV_KEY::BTN_0,
+                "button1" => EV_KEY::BTN_1,
+                "button2" => EV_KEY::BTN_2,
+                "button3" => EV_KEY::BTN_3,
+                "button4" => EV_KEY::BTN_4,
+                "button5" => EV_KEY::BTN_5,
+                "button6" => EV_KEY::BTN_6,
+                "button7" => EV_KEY::BTN_7,
+                "ringButton" => EV_KEY::BTN_8,
+                _ => {
+                    return Err(anyhow!(
+                        "'{}'不是有效的按钮，必须为button0至button7或ringButton",
+                        key
+                    ));
+                }
+            };
+            buttons.push(code);
+        }
+        let mut action = ImmediateKeymap::try_from(raw.action).context("action无效")?;
+        if let ImmediateKeymap::Passthrough
+        | ImmediateKeymap::Scroll
+        | ImmediateKeymap::Pad
+        | ImmediateKeymap::Fallback
+        | ImmediateKeymap::Gesture(_) = action
+        {
+            return Err(anyhow!(
+                "action不能为passthrough、scroll、pad、fallback或者tap/hold/doubleTap"
+            ));
+        }
+        action.expand_text(unicode_input).context("action无效")?;
+        let timeout = raw.timeout.unwrap_or(Self::DEFAULT_TIMEOUT);
+        if timeout == 0 {
+            return Err(anyhow!("timeout必须大于0"));
+        }
+        Ok(Self {
+            buttons,
+            action: action.try_into()?,
+            timeout: Duration::from_millis(timeout),
+        })
+    }
+}
+// 短按、长按与双击的按键映射；未配置的为Keymap::None
+pub struct Gesture {
+    pub tap: Keymap,
+    pub hold: Keymap, // 长按时按下，松开按钮时释放
+    pub double_tap: Keymap,
+    pub hold_threshold: Duration,
+    pub double_tap_interval: Duration,
+}
+// 宏的步骤
+#[derive(Clone, PartialEq, Debug)]
+pub enum MacroStep {
+    Combo(Vec<EV_KEY>), // 按下并释放按键组合
+    Delay(Duration),
+    Down(Vec<EV_KEY>),
+    Up(Vec<EV_KEY>),
+}
+// 虚拟鼠标的滚轮事件
+#[derive(Clone, Copy)]
+pub struct Wheel {
+    pub horizontal: bool,
+    pub amount: i32, // 高精度滚动量，正数表示向上/向右滚动
+}
+impl Wheel {
+    pub const HI_RES_PER_STEP: i32 = 120;
 }
 impl TryFrom<ImmediateKeymap> for Keymap {
     type Error = Error;
     fn try_from(value: ImmediateKeymap) -> Result<Self> {
         match value {
             ImmediateKeymap::Press(codes) => Ok(Self::Press(codes.clone())),
+            ImmediateKeymap::Macro(steps) => Ok(Self::Macro(steps)),
+            ImmediateKeymap::Type(_) => Err(anyhow!("type:没有转换为宏")),
+            ImmediateKeymap::Exec(argv) => Ok(Self::Exec(argv)),
+            ImmediateKeymap::Gesture(gesture) => Ok(Self::Gesture(Arc::new(Gesture {
+                tap: gesture.tap.try_into()?,
+                hold: gesture.hold.try_into()?,
+                double_tap: gesture.double_tap.try_into()?,
+                hold_threshold: gesture.hold_threshold,
+                double_tap_interval: gesture.double_tap_interval,
+            }))),
             ImmediateKeymap::SwitchSchema => Ok(Self::SwitchSchema),
+            ImmediateKeymap::CycleArea => Ok(Self::CycleArea),
+            ImmediateKeymap::PrecisionMode => Ok(Self::PrecisionMode),
+            ImmediateKeymap::TogglePrecisionMode => Ok(Self::TogglePrecisionMode),
+            ImmediateKeymap::ToggleStabilizer => Ok(Self::ToggleStabilizer),
+            ImmediateKeymap::TogglePenMode => Ok(Self::TogglePenMode),
+            ImmediateKeymap::Passthrough => Ok(Self::Passthrough),
+            ImmediateKeymap::Scroll => Ok(Self::Scroll),
+            ImmediateKeymap::Wheel(wheel) => Ok(Self::Wheel(wheel)),
+            ImmediateKeymap::Latch(codes) => Ok(Self::Latch(codes)),
+            ImmediateKeymap::Toggle(codes) => Ok(Self::Toggle(codes)),
+            ImmediateKeymap::Click(button) => Ok(Self::Click(button)),
+            ImmediateKeymap::DoubleClick(button) => Ok(Self::DoubleClick(button)),
+            ImmediateKeymap::Pad => Ok(Self::Pad),
             ImmediateKeymap::Fallback => Ok(Self::None),
             ImmediateKeymap::None => Ok(Self::None),
         }
@@ -241,20 +1200,137 @@ impl TryFrom<ImmediateKeymap> for Keymap {
 }
 impl TryFrom<ImmediateKeymapConfig> for KeymapConfig {
     type Error = Error;
-    fn try_from(value: ImmediateKeymapConfig) -> Result<Self> {
-        try_into! { value =>
+    fn try_from(mut value: ImmediateKeymapConfig) -> Result<Self> {
+        // 笔侧键未配置时保持原有功能
+        for keymap in [&mut value.stylus_button0, &mut value.stylus_button1] {
+            if let ImmediateKeymap::Fallback = keymap {
+                *keymap = ImmediateKeymap::Passthrough;
+            }
+        }
+        macro_rules! check_stylus_only {
+            ($($field:ident),+ $(,)?) => {
+                $(
+                    if let ImmediateKeymap::Passthrough = value.$field {
+                        return Err(anyhow!(concat!("'passthrough'只能用于笔侧键，不能用于", stringify!($field))));
+                    }
+                    if let ImmediateKeymap::Scroll = value.$field {
+                        return Err(anyhow!(concat!("'scroll'只能用于笔侧键，不能用于", stringify!($field))));
+                    }
+                )+
+            };
+        }
+        check_stylus_only! {
             button0, button1, button2, button3, button4, button5, button6, button7,
             ring0, ring1, ring_button,
         }
+        for keymap in [&value.stylus_button0, &value.stylus_button1] {
+            if let ImmediateKeymap::Pad = keymap {
+                return Err(anyhow!("'pad'不能用于笔侧键"));
+            }
+        }
+        for (name, keymap) in [
+            ("ring0", &value.ring0),
+            ("ring1", &value.ring1),
+            ("stylusButton0", &value.stylus_button0),
+            ("stylusButton1", &value.stylus_button1),
+        ] {
+            if let ImmediateKeymap::Gesture(_) = keymap {
+                return Err(anyhow!(
+                    "tap/hold/doubleTap只能用于按键面板的按钮，不能用于{}",
+                    name
+                ));
+            }
+        }
+        try_into! { value =>
+            button0, button1, button2, button3, button4, button5, button6, button7,
+            ring0, ring1, ring_button, stylus_button0, stylus_button1;
+            ring_acceleration: value.ring_acceleration,
+        }
+    }
+}
+impl KeymapConfig {
+    // 包括tap/hold/doubleTap中的按键映射
+    fn iter(&self) -> impl Iterator<Item = &Keymap> {
+        [
+            &self.button0,
+            &self.button1,
+            &self.button2,
+            &self.button3,
+            &self.button4,
+            &self.button5,
+            &self.button6,
+            &self.button7,
+            &self.ring0,
+            &self.ring1,
+            &self.ring_button,
+            &self.stylus_button0,
+            &self.stylus_button1,
+        ]
+        .into_iter()
+        .flat_map(|keymap| match keymap {
+            Keymap::Gesture(gesture) => vec![&gesture.tap, &gesture.hold, &gesture.double_tap],
+            _ => vec![keymap],
+        })
     }
 }
 impl Config {
+    const MAX_AXIS_SCALE: u16 = 64;
+
+    // 所有按键映射方案用到的普通按键码，虚拟键盘只启用这些按键码
+    pub fn keyboard_codes(&self) -> BTreeSet<EV_KEY> {
+        self.press_codes()
+            .filter(|code| !binding::is_consumer_key(*code))
+            .collect()
+    }
+
+    // 所有按键映射方案用到的多媒体/系统按键码，由单独的虚拟设备上报
+    pub fn consumer_codes(&self) -> BTreeSet<EV_KEY> {
+        self.press_codes()
+            .filter(|code| binding::is_consumer_key(*code))
+            .collect()
+    }
+
+    // 是否有方案使用pad，只有这时才需要创建虚拟按键面板
+    pub fn uses_pad(&self) -> bool {
+        self.all_keymaps()
+            .any(|keymap| matches!(keymap, Keymap::Pad))
+    }
+
+    // 所有方案与序列中的按键映射
+    fn all_keymaps(&self) -> impl Iterator<Item = &Keymap> {
+        all_keymaps(&self.keymaps, &self.sequences)
+    }
+
+    fn press_codes(&self) -> impl Iterator<Item = EV_KEY> {
+        self.all_keymaps().flat_map(|keymap| match keymap {
+            Keymap::Press(combo) => combo.codes.clone(),
+            Keymap::Latch(codes) | Keymap::Toggle(codes) => codes.to_vec(),
+            Keymap::Macro(steps) => steps
+                .iter()
+                .flat_map(|step| match step {
+                    MacroStep::Combo(codes) | MacroStep::Down(codes) | MacroStep::Up(codes) => {
+                        codes.clone()
+                    }
+                    MacroStep::Delay(_) => vec![],
+                })
+                .collect(),
+            _ => vec![],
+        })
+    }
+
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = std::fs::read_to_string(path).context("")?;
+        let content = std::fs::read_to_string(&path).context("")?;
         let raw: RawConfig = toml::from_str(&content).context("TOML解析失败")?;
         if raw.keymaps.is_empty() {
             return Err(anyhow!("没有配置keymap"));
         }
+        // 默认使用GTK/IBus的ctrl+shift+u输入十六进制码位，以空格确认
+        let unicode_input = raw.unicode_input.unwrap_or(RawUnicodeInputConfig::Hex {
+            start: None,
+            end: None,
+        });
+        let unicode_input =
+            UnicodeInputConfig::try_from(unicode_input).context("unicodeInput的配置无效")?;
 
         let iter = raw.keymaps.into_iter().map(|x| {
             ImmediateKeymapConfig::try_from(x)
@@ -264,6 +1340,7 @@ impl Config {
         let mut immediate_keymaps = vec![];
         for result in iter {
             let mut keymap = result?;
+            keymap.expand_text(&unicode_input)?;
             if let Some(prev) = prev {
                 keymap.resolve(prev);
             }
@@ -279,43 +1356,260 @@ impl Config {
             );
         }
 
-        macro_rules! check_map_values {
-            ($field:ident) => {
-                if let Some((min, max)) = raw.$field {
-                    if !(0f32..=1f32).contains(&min) {
-                        return Err(anyhow!(concat!(
-                            stringify!($field),
-                            "的最小值必须在0到1之间"
-                        )));
-                    }
-                    if !(0f32..=1f32).contains(&max) {
-                        return Err(anyhow!(concat!(
-                            stringify!($field),
-                            "的最大值必须在0到1之间"
-                        )));
-                    }
-                    if min >= max {
-                        return Err(anyhow!(concat!(
-                            stringify!($field),
-                            "的最小值必须小于最大值"
-                        )));
-                    }
+        let mut sequences: Vec<SequenceConfig> = vec![];
+        for (i, raw) in raw.sequences.into_iter().enumerate() {
+            let sequence = SequenceConfig::new(raw, &unicode_input)
+                .with_context(|| format!("第{}个sequence无效", i + 1))?;
+            // 一个序列是另一个序列的前缀时，无法判断是否应该等待后面的按钮
+            for (j, other) in sequences.iter().enumerate() {
+                if sequence.buttons.starts_with(&other.buttons)
+                    || other.buttons.starts_with(&sequence.buttons)
+                {
+                    return Err(anyhow!(
+                        "第{}个sequence与第{}个sequence重复，或者其中一个是另一个的前缀",
+                        i + 1,
+                        j + 1
+                    ));
                 }
-            };
+            }
+            sequences.push(sequence);
+        }
+
+        let drm_sysfs_root = raw
+            .drm_sysfs_root
+            .as_deref()
+            .unwrap_or(Path::new(display::DEFAULT_DRM_SYSFS_ROOT));
+        let (x_map, y_map) = resolve_map(
+            raw.x_map,
+            raw.y_map,
+            raw.map_output.as_deref(),
+            raw.output_layout.as_ref(),
+            drm_sysfs_root,
+        )?;
+        let mut areas = vec![];
+        for area in raw.areas {
+            let (x_map, y_map) = resolve_map(
+                area.x_map,
+                area.y_map,
+                area.output.as_deref(),
+                raw.output_layout.as_ref(),
+                drm_sysfs_root,
+            )
+            .context(format!("映射区域'{}'的配置无效", area.name))?;
+            areas.push(AreaConfig {
+                name: area.name,
+                x_map,
+                y_map,
+            });
+        }
+
+        let axis_scale = raw.axis_scale.unwrap_or(1);
+        if !(1..=Self::MAX_AXIS_SCALE).contains(&axis_scale) {
+            return Err(anyhow!("axisScale必须在1到{}之间", Self::MAX_AXIS_SCALE));
+        }
+
+        let precision_mode_scale = raw.precision_mode_scale.unwrap_or(0.25);
+        if precision_mode_scale <= 0f32 || precision_mode_scale > 1f32 {
+            return Err(anyhow!("precisionModeScale必须大于0且不大于1"));
+        }
+
+        let filter = match raw.filter {
+            Some(filter) => Some(FilterConfig::try_from(filter).context("filter的配置无效")?),
+            None => None,
+        };
+
+        let mut stabilizer = StabilizerConfig::default();
+        if let Some(raw) = raw.stabilizer {
+            stabilizer.length = raw.length.unwrap_or(stabilizer.length);
+            stabilizer.enabled = raw.enabled.unwrap_or(stabilizer.enabled);
+            if stabilizer.length <= 0f32 {
+                return Err(anyhow!("stabilizer.length必须大于0"));
+            }
+        }
+
+        let mut relative_mode = RelativeModeConfig::default();
+        if let Some(raw) = raw.relative_mode {
+            relative_mode.speed = raw.speed.unwrap_or(relative_mode.speed);
+            relative_mode.acceleration = raw.acceleration.unwrap_or(relative_mode.acceleration);
+            if relative_mode.speed <= 0f32 {
+                return Err(anyhow!("relativeMode.speed必须大于0"));
+            }
+            if relative_mode.acceleration < 0f32 {
+                return Err(anyhow!("relativeMode.acceleration不能小于0"));
+            }
+        }
+
+        let mut mouse_buttons = MouseButtonsConfig::default();
+        if let Some(raw) = raw.mouse_buttons {
+            mouse_buttons.stylus_button0 =
+                raw.stylus_button0.unwrap_or(mouse_buttons.stylus_button0);
+            mouse_buttons.stylus_button1 =
+                raw.stylus_button1.unwrap_or(mouse_buttons.stylus_button1);
         }
-        check_map_values!(x_map);
-        check_map_values!(y_map);
+
+        let mut scroll = ScrollConfig::default();
+        if let Some(raw) = raw.scroll {
+            scroll.speed = raw.speed.unwrap_or(scroll.speed);
+            scroll.invert_x = raw.invert_x.unwrap_or(scroll.invert_x);
+            scroll.invert_y = raw.invert_y.unwrap_or(scroll.invert_y);
+            if scroll.speed <= 0f32 {
+                return Err(anyhow!("scroll.speed必须大于0"));
+            }
+        }
+
+        let exec = resolve_exec(raw.exec, all_keymaps(&keymaps, &sequences), path.as_ref())?;
 
         Ok(Self {
             x_max_value: raw.x_max_value.unwrap_or(0),
             y_max_value: raw.y_max_value.unwrap_or(0),
-            x_map: raw.x_map,
-            y_map: raw.y_map,
+            axis_scale: axis_scale as i32,
+            x_map,
+            y_map,
+            areas,
+            precision_mode_scale,
+            filter,
+            stabilizer,
+            pen_mode: raw.pen_mode.unwrap_or_default(),
+            relative_mode,
+            digitizer_type: raw.digitizer_type.unwrap_or_default(),
+            mouse_buttons,
+            scroll,
+            exec,
+            sequences,
+            drm_sysfs_root: raw.drm_sysfs_root,
             keymaps,
         })
     }
 }
 
+fn all_keymaps<'a>(
+    keymaps: &'a [KeymapConfig],
+    sequences: &'a [SequenceConfig],
+) -> impl Iterator<Item = &'a Keymap> {
+    keymaps
+        .iter()
+        .flat_map(|x| x.iter())
+        .chain(sequences.iter().map(|x| &x.action))
+}
+
+// 校验exec的配置：以root身份运行的驱动不能被可写的配置文件用来以root身份运行任意程序
+//
+// 运行命令的用户、用户组与环境变量都来自配置文件，因此只要配置了exec，配置文件就必须属于root且其他用户不可写；
+// allowlist只是在此基础上进一步限制可以运行的程序
+fn resolve_exec<'a>(
+    raw: Option<RawExecConfig>,
+    keymaps: impl Iterator<Item = &'a Keymap>,
+    conf_path: &Path,
+) -> Result<Option<ExecConfig>> {
+    let programs = keymaps
+        .filter_map(|keymap| match keymap {
+            Keymap::Exec(argv) => Some(argv[0].as_str()),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+    let raw = match raw {
+        Some(raw) => raw,
+        None if programs.is_empty() => return Ok(None),
+        None => return Err(anyhow!("使用exec需要配置[exec]")),
+    };
+    if raw.uid == 0 || raw.gid == 0 {
+        return Err(anyhow!("exec.uid与exec.gid不能为0"));
+    }
+    if !programs.is_empty() {
+        check_root_owned(conf_path).context("使用exec时，配置文件必须属于root且其他用户不可写")?;
+    }
+    if let Some(allowlist) = &raw.allowlist {
+        check_root_owned(allowlist).context("exec.allowlist无效")?;
+        let content = std::fs::read_to_string(allowlist)
+            .with_context(|| format!("无法读取{}", allowlist.display()))?;
+        let mut allowed = BTreeSet::new();
+        for line in content.lines().map(|line| line.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // 程序名称会通过PATH查找，只允许绝对路径
+            if !Path::new(line).is_absolute() {
+                return Err(anyhow!("exec.allowlist中的'{}'不是绝对路径", line));
+            }
+            allowed.insert(line);
+        }
+        for program in &programs {
+            if !Path::new(program).is_absolute() {
+                return Err(anyhow!("exec的程序'{}'必须是绝对路径", program));
+            }
+            if !allowed.contains(program) {
+                return Err(anyhow!("程序'{}'不在exec.allowlist中", program));
+            }
+        }
+    }
+    let mut env = raw.env.unwrap_or_default();
+    env.entry("PATH".to_string())
+        .or_insert_with(|| ExecConfig::DEFAULT_PATH.to_string());
+    let timeout = match raw.timeout {
+        Some(0) => None,
+        Some(ms) => Some(Duration::from_millis(ms)),
+        None => Some(ExecConfig::DEFAULT_TIMEOUT),
+    };
+    Ok(Some(ExecConfig {
+        uid: raw.uid,
+        gid: raw.gid,
+        env,
+        timeout,
+    }))
+}
+
+// 文件必须属于root，且用户组与其他用户不可写
+fn check_root_owned(path: &Path) -> Result<()> {
+    let metadata =
+        std::fs::metadata(path).with_context(|| format!("无法读取{}的属性", path.display()))?;
+    if metadata.uid() != 0 {
+        return Err(anyhow!("{}不属于root", path.display()));
+    }
+    if metadata.mode() & 0o022 != 0 {
+        return Err(anyhow!("{}可以被其他用户修改", path.display()));
+    }
+    Ok(())
+}
+
+type AreaMap = (Option<(f32, f32)>, Option<(f32, f32)>);
+
+// 校验并解析映射区域；若配置了显示输出，则根据显示输出计算出X轴与Y轴的比例映射
+fn resolve_map(
+    x_map: Option<(f32, f32)>,
+    y_map: Option<(f32, f32)>,
+    output: Option<&str>,
+    layout: Option<&display::OutputLayout>,
+    drm_sysfs_root: &Path,
+) -> Result<AreaMap> {
+    check_map_values("x_map", x_map)?;
+    check_map_values("y_map", y_map)?;
+    let connector = match output {
+        Some(connector) => connector,
+        None => return Ok((x_map, y_map)),
+    };
+    if x_map.is_some() || y_map.is_some() {
+        return Err(anyhow!("显示输出不能与xMap、yMap同时配置"));
+    }
+    let outputs = display::scan_outputs(drm_sysfs_root).context("无法扫描显示输出")?;
+    let (x_map, y_map) = display::output_map(&outputs, connector, layout)?;
+    Ok((Some(x_map), Some(y_map)))
+}
+
+fn check_map_values(field: &str, map: Option<(f32, f32)>) -> Result<()> {
+    if let Some((min, max)) = map {
+        if !(0f32..=1f32).contains(&min) {
+            return Err(anyhow!("{}的最小值必须在0到1之间", field));
+        }
+        if !(0f32..=1f32).contains(&max) {
+            return Err(anyhow!("{}的最大值必须在0到1之间", field));
+        }
+        if min >= max {
+            return Err(anyhow!("{}的最小值必须小于最大值", field));
+        }
+    }
+    Ok(())
+}
+
 type ConfigChangeCallback = Box<dyn FnMut(Arc<Config>) + Send + Sync>;
 
 pub struct WatchConfigChangeTask {
@@ -451,3 +1745,346 @@ impl WatchConfigChangeTask {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Wrapper {
+        binding: RawBinding,
+    }
+
+    fn keymap(value: &str) -> Result<ImmediateKeymap> {
+        ImmediateKeymap::try_from(RawKeys::String(value.to_string()))
+    }
+
+    // 以TOML的形式解析一个按键映射，例如"{ macro = [\"a\"] }"
+    fn binding(toml: &str) -> Result<ImmediateKeymap> {
+        let wrapper: Wrapper = toml::from_str(&format!("binding = {}", toml))?;
+        ImmediateKeymap::try_from(wrapper.binding)
+    }
+
+    fn macro_steps(keymap: ImmediateKeymap) -> Vec<MacroStep> {
+        match keymap {
+            ImmediateKeymap::Macro(steps) => steps.to_vec(),
+            _ => panic!("不是宏"),
+        }
+    }
+
+    #[test]
+    fn macro_from_comma_separated_combos() {
+        let steps = macro_steps(keymap("ctrl+a, ctrl+c").unwrap());
+        assert_eq!(
+            steps,
+            [
+                MacroStep::Combo(vec![EV_KEY::KEY_LEFTCTRL, EV_KEY::KEY_A]),
+                MacroStep::Combo(vec![EV_KEY::KEY_LEFTCTRL, EV_KEY::KEY_C]),
+            ]
+        );
+    }
+
+    #[test]
+    fn macro_from_table() {
+        let steps = macro_steps(
+            binding(r#"{ macro = ["down:shift", "a", "delay:50", "up:shift, b"] }"#).unwrap(),
+        );
+        assert_eq!(
+            steps,
+            [
+                MacroStep::Down(vec![EV_KEY::KEY_LEFTSHIFT]),
+                MacroStep::Combo(vec![EV_KEY::KEY_A]),
+                MacroStep::Delay(Duration::from_millis(50)),
+                MacroStep::Up(vec![EV_KEY::KEY_LEFTSHIFT]),
+                MacroStep::Combo(vec![EV_KEY::KEY_B]),
+            ]
+        );
+    }
+
+    fn typed_text(value: &str) -> String {
+        match keymap(value).unwrap() {
+            ImmediateKeymap::Type(text) => text.to_string(),
+            _ => panic!("不是type:"),
+        }
+    }
+
+    #[test]
+    fn type_text() {
+        assert_eq!(typed_text("type:Hello, World"), "Hello, World");
+        assert_eq!(typed_text(r#"Type: " a b ""#), " a b ");
+        assert_eq!(typed_text(r#"  type:"quoted"  "#), "quoted");
+        assert_eq!(typed_text(r#"type:say "hi""#), r#"say "hi""#);
+    }
+
+    #[test]
+    fn gesture_table() {
+        let keymap = binding(r#"{ tap = "b", hold = "space", doubleTap = "type:x" }"#).unwrap();
+        let ImmediateKeymap::Gesture(gesture) = keymap else {
+            panic!("不是tap/hold/doubleTap");
+        };
+        assert!(matches!(gesture.tap, ImmediateKeymap::Press(_)));
+        assert!(matches!(gesture.hold, ImmediateKeymap::Press(_)));
+        assert!(matches!(gesture.double_tap, ImmediateKeymap::Type(_)));
+        assert_eq!(gesture.hold_threshold, Duration::from_millis(300));
+        assert_eq!(gesture.double_tap_interval, Duration::from_millis(250));
+
+        let keymap = binding(r#"{ hold = "a", holdThreshold = 500, doubleTapInterval = 100 }"#);
+        let ImmediateKeymap::Gesture(gesture) = keymap.unwrap() else {
+            panic!("不是tap/hold/doubleTap");
+        };
+        assert!(matches!(gesture.tap, ImmediateKeymap::None));
+        assert_eq!(gesture.hold_threshold, Duration::from_millis(500));
+        assert_eq!(gesture.double_tap_interval, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn gesture_errors() {
+        assert!(binding(r#"{ holdThreshold = 500 }"#).is_err());
+        assert!(binding(r#"{ tap = "a", holdThreshold = 0 }"#).is_err());
+        assert!(binding(r#"{ tap = "passthrough" }"#).is_err());
+        assert!(binding(r#"{ tap = "pad" }"#).is_err());
+        assert!(binding(r#"{ tap = { hold = "a" } }"#).is_err());
+    }
+
+    fn sequence(toml: &str) -> Result<SequenceConfig> {
+        let raw: RawSequenceConfig = toml::from_str(toml)?;
+        let unicode_input = UnicodeInputConfig::Hex {
+            start: vec![EV_KEY::KEY_LEFTCTRL, EV_KEY::KEY_LEFTSHIFT, EV_KEY::KEY_U],
+            end: vec![EV_KEY::KEY_SPACE],
+        };
+        SequenceConfig::new(raw, &unicode_input)
+    }
+
+    #[test]
+    fn sequence_config() {
+        let conf = sequence(
+            r#"keys = ["button0", "ringButton"]
+action = "type:é""#,
+        )
+        .unwrap();
+        assert_eq!(conf.buttons, [EV_KEY::BTN_0, EV_KEY::BTN_8]);
+        assert!(matches!(conf.action, Keymap::Macro(_)));
+        assert_eq!(conf.timeout, Duration::from_millis(800));
+
+        let conf = sequence(
+            r#"keys = ["button1", "button1", "button2"]
+action = "ctrl+z"
+timeout = 300"#,
+        )
+        .unwrap();
+        assert_eq!(conf.buttons, [EV_KEY::BTN_1, EV_KEY::BTN_1, EV_KEY::BTN_2]);
+        assert!(matches!(conf.action, Keymap::Press(_)));
+        assert_eq!(conf.timeout, Duration::from_millis(300));
+    }
+
+    #[test]
+    fn sequence_errors() {
+        assert!(
+            sequence(
+                r#"keys = ["button0"]
+action = "a""#
+            )
+            .is_err()
+        );
+        assert!(
+            sequence(
+                r#"keys = ["button0", "button8"]
+action = "a""#
+            )
+            .is_err()
+        );
+        assert!(
+            sequence(
+                r#"keys = ["button0", "button1"]
+action = "passthrough""#
+            )
+            .is_err()
+        );
+        assert!(
+            sequence(
+                r#"keys = ["button0", "button1"]
+action = { tap = "a" }"#
+            )
+            .is_err()
+        );
+        assert!(
+            sequence(
+                r#"keys = ["button0", "button1"]
+action = "a"
+timeout = 0"#
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn macro_errors() {
+        assert!(binding(r#"{ macro = [] }"#).is_err());
+        assert!(binding(r#"{ macro = ["delay:abc"] }"#).is_err());
+        assert!(binding(r#"{ macro = ["delay:10+a"] }"#).is_err());
+        assert!(binding(r#"{ macro = ["down:"] }"#).is_err());
+        assert!(binding(r#"{ macro = ["switchSchema"] }"#).is_err());
+        assert!(binding(r#"{ macro = ["a"], holdTime = 100 }"#).is_err());
+        assert!(keymap("ctrl+a, btn_left").is_err());
+    }
+
+    // 在临时目录中创建指定权限的文件，测试结束时删除
+    struct TempFile(PathBuf);
+    impl TempFile {
+        fn new(name: &str, content: &str, mode: u32) -> Self {
+            use std::os::unix::fs::PermissionsExt;
+            let path = std::env::temp_dir().join(format!("parblo-{}-{}", name, std::process::id()));
+            std::fs::write(&path, content).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+            Self(path)
+        }
+    }
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn exec_config(toml: &str) -> RawExecConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn exec_keymaps(programs: &[&str]) -> Vec<Keymap> {
+        programs
+            .iter()
+            .map(|program| Keymap::Exec(Arc::new(vec![program.to_string()])))
+            .collect()
+    }
+
+    // 临时文件只有以root身份运行测试时才属于root
+    fn is_root_owned(file: &TempFile) -> bool {
+        std::fs::metadata(&file.0).unwrap().uid() == 0
+    }
+
+    #[test]
+    fn resolve_exec_without_bindings() {
+        let conf = TempFile::new("exec-unused.toml", "", 0o666);
+        assert!(
+            resolve_exec(None, exec_keymaps(&[]).iter(), &conf.0)
+                .unwrap()
+                .is_none()
+        );
+        // 未使用exec时不检查配置文件
+        let raw = exec_config("uid = 1000\ngid = 1000");
+        assert!(resolve_exec(Some(raw), exec_keymaps(&[]).iter(), &conf.0).is_ok());
+    }
+
+    #[test]
+    fn resolve_exec_requires_exec_table_and_non_root() {
+        let keymaps = exec_keymaps(&["/usr/bin/true"]);
+        assert!(resolve_exec(None, keymaps.iter(), Path::new("/")).is_err());
+        let raw = exec_config("uid = 0\ngid = 1000");
+        assert!(resolve_exec(Some(raw), keymaps.iter(), Path::new("/")).is_err());
+        let raw = exec_config("uid = 1000\ngid = 0");
+        assert!(resolve_exec(Some(raw), keymaps.iter(), Path::new("/")).is_err());
+    }
+
+    #[test]
+    fn resolve_exec_rejects_writable_config_even_with_allowlist() {
+        let allowlist = TempFile::new("exec-allowlist-writable", "/usr/bin/true\n", 0o644);
+        let conf = TempFile::new("exec-writable.toml", "", 0o666);
+        let raw = exec_config(&format!(
+            "uid = 1000\ngid = 6\nallowlist = {:?}",
+            allowlist.0
+        ));
+        let keymaps = exec_keymaps(&["/usr/bin/true"]);
+        assert!(resolve_exec(Some(raw), keymaps.iter(), &conf.0).is_err());
+    }
+
+    #[test]
+    fn resolve_exec_checks_allowlist() {
+        let conf = TempFile::new("exec-checked.toml", "", 0o644);
+        if !is_root_owned(&conf) {
+            return;
+        }
+        let allowlist = TempFile::new("exec-allowlist-checked", "# 注释\n\n/usr/bin/true\n", 0o644);
+        let raw = || {
+            exec_config(&format!(
+                "uid = 1000\ngid = 1000\nallowlist = {:?}",
+                allowlist.0
+            ))
+        };
+        let exec = resolve_exec(
+            Some(raw()),
+            exec_keymaps(&["/usr/bin/true"]).iter(),
+            &conf.0,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(exec.env["PATH"], ExecConfig::DEFAULT_PATH);
+        assert_eq!(exec.timeout, Some(ExecConfig::DEFAULT_TIMEOUT));
+        let keymaps = exec_keymaps(&["/usr/bin/true", "/bin/sh"]);
+        assert!(resolve_exec(Some(raw()), keymaps.iter(), &conf.0).is_err());
+        let keymaps = exec_keymaps(&["true"]);
+        assert!(resolve_exec(Some(raw()), keymaps.iter(), &conf.0).is_err());
+    }
+
+    #[test]
+    fn resolve_exec_rejects_bad_allowlist() {
+        let conf = TempFile::new("exec-bad.toml", "", 0o644);
+        if !is_root_owned(&conf) {
+            return;
+        }
+        let keymaps = exec_keymaps(&["/usr/bin/true"]);
+        for (name, content, mode) in [
+            ("exec-allowlist-relative", "true\n/usr/bin/true\n", 0o644),
+            ("exec-allowlist-group-writable", "/usr/bin/true\n", 0o664),
+        ] {
+            let allowlist = TempFile::new(name, content, mode);
+            let raw = exec_config(&format!(
+                "uid = 1000\ngid = 1000\nallowlist = {:?}",
+                allowlist.0
+            ));
+            assert!(resolve_exec(Some(raw), keymaps.iter(), &conf.0).is_err());
+        }
+    }
+
+    #[test]
+    fn ring_acceleration_multiplier() {
+        let conf = RingAccelerationConfig {
+            threshold: Duration::from_millis(100),
+            factor: 0.5,
+            max: 4,
+        };
+        assert_eq!(conf.multiplier(Duration::from_millis(100)), 1.0);
+        assert_eq!(conf.multiplier(Duration::from_millis(150)), 1.0);
+        // 间隔为阈值的1/n时，倍数为1+factor*(n-1)
+        assert!((conf.multiplier(Duration::from_millis(50)) - 1.5).abs() < 1e-4);
+        assert!((conf.multiplier(Duration::from_millis(25)) - 2.5).abs() < 1e-4);
+        assert!(conf.multiplier(Duration::from_millis(99)) > 1.0);
+        // 间隔为0时不会除以0，倍数不超过max
+        assert_eq!(conf.multiplier(Duration::ZERO), 4.0);
+    }
+
+    fn wheel(value: &str) -> Result<(bool, i32)> {
+        match keymap(value)? {
+            ImmediateKeymap::Wheel(wheel) => Ok((wheel.horizontal, wheel.amount)),
+            _ => panic!("不是滚轮"),
+        }
+    }
+
+    #[test]
+    fn wheel_directions_and_steps() {
+        assert_eq!(wheel("scroll:up").unwrap(), (false, 120));
+        assert_eq!(wheel("scroll:down").unwrap(), (false, -120));
+        assert_eq!(wheel("Scroll: Right").unwrap(), (true, 120));
+        assert_eq!(wheel("scroll:left:3").unwrap(), (true, -360));
+        assert_eq!(wheel("scroll:down:0.5").unwrap(), (false, -60));
+    }
+
+    #[test]
+    fn wheel_errors() {
+        assert!(keymap("scroll:up:0").is_err());
+        assert!(keymap("scroll:up:-1").is_err());
+        assert!(keymap("scroll:up:0.001").is_err());
+        assert!(keymap("scroll:up:abc").is_err());
+        assert!(keymap("scroll:up:1:2").is_err());
+        assert!(keymap("scroll:sideways").is_err());
+        assert!(keymap("scroll:").is_err());
+    }
+}
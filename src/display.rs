@@ -0,0 +1,380 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::warn;
+
+pub const DEFAULT_DRM_SYSFS_ROOT: &str = "/sys/class/drm";
+
+const EDID_HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+const EDID_BLOCK_SIZE: usize = 128;
+
+// 已连接的显示输出
+#[derive(Clone, Debug)]
+pub struct Output {
+    // 连接器名称，例如"HDMI-A-1"
+    pub connector: String,
+
+    // 首选分辨率
+    pub width: u32,
+    pub height: u32,
+
+    // EDID中的显示器名称
+    pub name: Option<String>,
+
+    // EDID中的物理尺寸（毫米）
+    pub size_mm: Option<(u32, u32)>,
+}
+
+// 扫描DRM的sysfs目录（通常为/sys/class/drm），按连接器名称排序返回所有已连接的显示输出
+pub fn scan_outputs<P: AsRef<Path>>(root: P) -> Result<Vec<Output>> {
+    let root = root.as_ref();
+    let entries =
+        std::fs::read_dir(root).with_context(|| format!("无法读取目录{}", root.display()))?;
+    let mut outputs = vec![];
+    for entry in entries {
+        let entry = entry.with_context(|| format!("无法读取目录{}中的信息", root.display()))?;
+        let path = entry.path();
+        // 连接器目录的名称形如"card0-HDMI-A-1"
+        let dirname = entry.file_name().to_string_lossy().to_string();
+        let connector = match dirname.split_once('-') {
+            Some((card, connector)) if card.starts_with("card") => connector.to_string(),
+            _ => continue,
+        };
+        let status = match std::fs::read_to_string(path.join("status")) {
+            Ok(status) => status,
+            Err(_) => continue,
+        };
+        if status.trim() != "connected" {
+            continue;
+        }
+
+        let edid = std::fs::read(path.join("edid")).unwrap_or_default();
+        let edid = Edid::parse(&edid);
+        let modes = std::fs::read_to_string(path.join("modes")).unwrap_or_default();
+        let mode = modes.lines().next().and_then(parse_mode);
+        let (width, height) = match mode.or(edid.as_ref().and_then(|x| x.preferred_mode)) {
+            Some(mode) => mode,
+            None => continue,
+        };
+        outputs.push(Output {
+            connector,
+            width,
+            height,
+            name: edid.as_ref().and_then(|x| x.name.clone()),
+            size_mm: edid.as_ref().and_then(|x| x.size_mm),
+        });
+    }
+    outputs.sort_by(|a, b| a.connector.cmp(&b.connector));
+    Ok(outputs)
+}
+
+// 显示输出左上角在桌面中的位置（像素），以连接器名称为键
+pub type OutputLayout = BTreeMap<String, (i32, i32)>;
+
+// 计算指定显示输出在整个桌面中所占的比例区间，返回值可直接用作x_map与y_map
+//
+// DRM本身不提供显示器之间的相对位置，需要由layout给出每个已连接的显示输出的位置；
+// 未配置时假定所有显示输出按连接器名称的顺序从左到右排列，并且顶部对齐
+pub fn output_map(
+    outputs: &[Output],
+    connector: &str,
+    layout: Option<&OutputLayout>,
+) -> Result<((f32, f32), (f32, f32))> {
+    let mut rects = Vec::with_capacity(outputs.len());
+    match layout {
+        Some(layout) => {
+            for output in outputs {
+                let (x, y) = layout.get(&output.connector).with_context(|| {
+                    format!("outputLayout中缺少已连接的显示输出'{}'", output.connector)
+                })?;
+                rects.push((output, *x as i64, *y as i64));
+            }
+        }
+        None => {
+            if outputs.len() > 1 {
+                warn!(
+                    "未配置outputLayout，假定显示输出按连接器名称的顺序从左到右排列并顶部对齐: {}",
+                    outputs
+                        .iter()
+                        .map(|x| x.connector.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            let mut offset = 0;
+            for output in outputs {
+                rects.push((output, offset, 0));
+                offset += output.width as i64;
+            }
+        }
+    }
+    let left = rects.iter().map(|(_, x, _)| *x).min().unwrap_or(0);
+    let top = rects.iter().map(|(_, _, y)| *y).min().unwrap_or(0);
+    let right = rects
+        .iter()
+        .map(|(output, x, _)| x + output.width as i64)
+        .max()
+        .unwrap_or(0);
+    let bottom = rects
+        .iter()
+        .map(|(output, _, y)| y + output.height as i64)
+        .max()
+        .unwrap_or(0);
+    let (desktop_width, desktop_height) = ((right - left) as f32, (bottom - top) as f32);
+    if desktop_width <= 0f32 || desktop_height <= 0f32 {
+        return Err(anyhow!(
+            "桌面的尺寸为0，无法计算显示输出'{}'的映射区域",
+            connector
+        ));
+    }
+    let (output, x, y) = rects
+        .iter()
+        .find(|(output, _, _)| output.connector == connector)
+        .with_context(|| format!("找不到已连接的显示输出'{}'", connector))?;
+    let x_map = (
+        (x - left) as f32 / desktop_width,
+        (x - left + output.width as i64) as f32 / desktop_width,
+    );
+    // y_map的比例区间以绘图板坐标翻转前的方向为准，即从屏幕底部开始计算
+    let y_map = (
+        1f32 - (y - top + output.height as i64) as f32 / desktop_height,
+        1f32 - (y - top) as f32 / desktop_height,
+    );
+    Ok((x_map, y_map))
+}
+
+fn parse_mode(mode: &str) -> Option<(u32, u32)> {
+    // 模式名称形如"1920x1080"，隔行扫描模式带有"i"后缀
+    let (width, height) = mode.trim().split_once('x')?;
+    let height = height.trim_end_matches(|c: char| !c.is_ascii_digit());
+    let mode = (width.parse().ok()?, height.parse().ok()?);
+    // 忽略尺寸为0的无效模式
+    (mode.0 > 0 && mode.1 > 0).then_some(mode)
+}
+
+struct Edid {
+    name: Option<String>,
+    size_mm: Option<(u32, u32)>,
+    preferred_mode: Option<(u32, u32)>,
+}
+impl Edid {
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < EDID_BLOCK_SIZE || data[..8] != EDID_HEADER {
+            return None;
+        }
+        let mut edid = Self {
+            name: None,
+            size_mm: None,
+            preferred_mode: None,
+        };
+        // 基础块中的屏幕尺寸单位为厘米
+        if data[21] > 0 && data[22] > 0 {
+            edid.size_mm = Some((data[21] as u32 * 10, data[22] as u32 * 10));
+        }
+        // 基础块中包含4个18字节的描述符，第一个描述符为首选的时序描述符
+        for (i, desc) in data[54..126].chunks_exact(18).enumerate() {
+            if desc[0] != 0 || desc[1] != 0 {
+                if i == 0 {
+                    let width = desc[2] as u32 | ((desc[4] as u32 & 0xf0) << 4);
+                    let height = desc[5] as u32 | ((desc[7] as u32 & 0xf0) << 4);
+                    if width > 0 && height > 0 {
+                        edid.preferred_mode = Some((width, height));
+                    }
+                    let width_mm = desc[12] as u32 | ((desc[14] as u32 & 0xf0) << 4);
+                    let height_mm = desc[13] as u32 | ((desc[14] as u32 & 0x0f) << 8);
+                    if width_mm > 0 && height_mm > 0 {
+                        edid.size_mm = Some((width_mm, height_mm));
+                    }
+                }
+                continue;
+            }
+            // 0xfc为显示器名称描述符，名称以0x0a结尾
+            if desc[3] == 0xfc {
+                let name = desc[5..]
+                    .iter()
+                    .take_while(|c| **c != 0x0a)
+                    .map(|c| *c as char)
+                    .collect::<String>();
+                let name = name.trim();
+                if !name.is_empty() {
+                    edid.name = Some(name.to_string());
+                }
+            }
+        }
+        Some(edid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // 在临时目录中构造DRM的sysfs目录，测试结束时删除
+    struct FakeDrm(PathBuf);
+    impl FakeDrm {
+        fn new(name: &str) -> Self {
+            let root =
+                std::env::temp_dir().join(format!("parblo-drm-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(&root).unwrap();
+            Self(root)
+        }
+
+        fn connector(&self, dirname: &str, status: &str, modes: &str, edid: &[u8]) {
+            let dir = self.0.join(dirname);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("status"), status).unwrap();
+            std::fs::write(dir.join("modes"), modes).unwrap();
+            std::fs::write(dir.join("edid"), edid).unwrap();
+        }
+    }
+    impl Drop for FakeDrm {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn edid(name: &str, mode: (u32, u32), size_mm: (u32, u32)) -> Vec<u8> {
+        let mut data = vec![0u8; EDID_BLOCK_SIZE];
+        data[..8].copy_from_slice(&EDID_HEADER);
+        data[21] = (size_mm.0 / 10) as u8;
+        data[22] = (size_mm.1 / 10) as u8;
+        let timing = &mut data[54..72];
+        timing[0] = 0x01;
+        timing[2] = mode.0 as u8;
+        timing[4] = ((mode.0 >> 8) << 4) as u8;
+        timing[5] = mode.1 as u8;
+        timing[7] = ((mode.1 >> 8) << 4) as u8;
+        timing[12] = size_mm.0 as u8;
+        timing[13] = size_mm.1 as u8;
+        timing[14] = (((size_mm.0 >> 8) << 4) | (size_mm.1 >> 8)) as u8;
+        let desc = &mut data[72..90];
+        desc[3] = 0xfc;
+        let mut text = name.as_bytes().to_vec();
+        text.push(0x0a);
+        text.resize(13, b' ');
+        desc[5..].copy_from_slice(&text);
+        data
+    }
+
+    #[test]
+    fn scan_outputs_reads_connected_outputs() {
+        let drm = FakeDrm::new("scan");
+        drm.connector(
+            "card0-HDMI-A-1",
+            "connected\n",
+            "1920x1080\n1280x720\n",
+            &edid("DELL P2419H", (1920, 1080), (527, 296)),
+        );
+        // 没有modes时使用EDID中的首选分辨率
+        drm.connector(
+            "card0-DP-1",
+            "connected\n",
+            "",
+            &edid("LG HDR 4K", (3840, 2160), (600, 340)),
+        );
+        drm.connector("card0-DP-2", "disconnected\n", "2560x1440\n", &[]);
+        // 尺寸为0的模式与没有有效模式的显示输出被忽略
+        drm.connector("card0-VGA-1", "connected\n", "0x0\n", &[]);
+        drm.connector("version", "connected\n", "1920x1080\n", &[]);
+
+        let outputs = scan_outputs(&drm.0).unwrap();
+        let connectors = outputs
+            .iter()
+            .map(|x| x.connector.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(connectors, ["DP-1", "HDMI-A-1"]);
+        assert_eq!((outputs[0].width, outputs[0].height), (3840, 2160));
+        assert_eq!(outputs[0].name.as_deref(), Some("LG HDR 4K"));
+        assert_eq!(outputs[0].size_mm, Some((600, 340)));
+        assert_eq!((outputs[1].width, outputs[1].height), (1920, 1080));
+        assert_eq!(outputs[1].name.as_deref(), Some("DELL P2419H"));
+        assert_eq!(outputs[1].size_mm, Some((527, 296)));
+    }
+
+    #[test]
+    fn scan_outputs_fails_without_root() {
+        assert!(scan_outputs("/nonexistent/parblo-drm").is_err());
+    }
+
+    #[test]
+    fn parse_mode_accepts_interlaced_and_rejects_zero() {
+        assert_eq!(parse_mode("1920x1080"), Some((1920, 1080)));
+        assert_eq!(parse_mode("1920x1080i"), Some((1920, 1080)));
+        assert_eq!(parse_mode("0x0"), None);
+        assert_eq!(parse_mode("1920x0"), None);
+        assert_eq!(parse_mode("invalid"), None);
+    }
+
+    #[test]
+    fn edid_parse_rejects_invalid_data() {
+        assert!(Edid::parse(&[]).is_none());
+        assert!(Edid::parse(&[0u8; EDID_BLOCK_SIZE]).is_none());
+    }
+
+    fn output(connector: &str, width: u32, height: u32) -> Output {
+        Output {
+            connector: connector.to_string(),
+            width,
+            height,
+            name: None,
+            size_mm: None,
+        }
+    }
+
+    #[test]
+    fn output_map_places_outputs_left_to_right() {
+        let outputs = [output("DP-1", 2560, 1440), output("HDMI-A-1", 1280, 720)];
+        let (x_map, y_map) = output_map(&outputs, "DP-1", None).unwrap();
+        assert_eq!(x_map, (0.0, 2560.0 / 3840.0));
+        assert_eq!(y_map, (0.0, 1.0));
+        let (x_map, y_map) = output_map(&outputs, "HDMI-A-1", None).unwrap();
+        assert_eq!(x_map, (2560.0 / 3840.0, 1.0));
+        assert_eq!(y_map, (0.5, 1.0));
+        assert!(output_map(&outputs, "DP-2", None).is_err());
+    }
+
+    #[test]
+    fn output_map_rejects_zero_sized_desktop() {
+        assert!(output_map(&[output("DP-1", 0, 0)], "DP-1", None).is_err());
+    }
+
+    #[test]
+    fn output_map_uses_layout() {
+        // 笔记本的内置屏幕在左侧，外接显示器在右侧并且底部对齐
+        let outputs = [output("DP-1", 2560, 1440), output("eDP-1", 1280, 720)];
+        let layout = OutputLayout::from([
+            ("eDP-1".to_string(), (0, 720)),
+            ("DP-1".to_string(), (1280, 0)),
+        ]);
+        let (x_map, y_map) = output_map(&outputs, "eDP-1", Some(&layout)).unwrap();
+        assert_eq!(x_map, (0.0, 1280.0 / 3840.0));
+        assert_eq!(y_map, (0.0, 0.5));
+        let (x_map, y_map) = output_map(&outputs, "DP-1", Some(&layout)).unwrap();
+        assert_eq!(x_map, (1280.0 / 3840.0, 1.0));
+        assert_eq!(y_map, (0.0, 1.0));
+    }
+
+    #[test]
+    fn output_map_layout_allows_negative_positions() {
+        let outputs = [output("DP-1", 1920, 1080), output("HDMI-A-1", 1920, 1080)];
+        let layout = OutputLayout::from([
+            ("DP-1".to_string(), (0, 0)),
+            ("HDMI-A-1".to_string(), (0, -1080)),
+        ]);
+        let (x_map, y_map) = output_map(&outputs, "HDMI-A-1", Some(&layout)).unwrap();
+        assert_eq!(x_map, (0.0, 1.0));
+        assert_eq!(y_map, (0.5, 1.0));
+    }
+
+    #[test]
+    fn output_map_layout_must_cover_connected_outputs() {
+        let outputs = [output("DP-1", 1920, 1080), output("HDMI-A-1", 1920, 1080)];
+        let layout = OutputLayout::from([("DP-1".to_string(), (0, 0))]);
+        assert!(output_map(&outputs, "DP-1", Some(&layout)).is_err());
+    }
+}
@@ -1,17 +1,25 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{Context, Result, anyhow};
-use evdev_rs::enums::{EV_ABS, EV_KEY, EV_SYN, EventCode, EventType, InputProp};
+use evdev_rs::enums::{EV_ABS, EV_KEY, EV_MSC, EV_REL, EV_SYN, EventCode, EventType, InputProp};
 use evdev_rs::{
     AbsInfo, Device as EventDevice, DeviceWrapper, InputEvent, TimeVal, UInputDevice, UninitDevice,
 };
+use nix::time::{ClockId, clock_gettime};
 use parking_lot::Mutex;
 use rusb::{DeviceHandle as UsbDeviceHandle, Error as UsbError, UsbContext};
 
+use crate::binding;
 use crate::cancel::CancelToken;
-use crate::config::{Config, Keymap, WatchConfigChangeTask};
+use crate::config::{
+    Config, DigitizerType, Gesture, Keymap, KeymapConfig, PenMode, WatchConfigChangeTask, Wheel,
+};
+use crate::exec;
+use crate::filter::PositionFilter;
+use crate::schedule::KeyQueue;
+use crate::stabilizer::Stabilizer;
 use crate::{debug, info, warn};
 
 const VENDOR_ID: u16 = 0x0483;
@@ -22,9 +30,15 @@ const OUT_ENDPOINT: u8 = 0x03;
 const HANDSHAKE_USAGE_BUF_SIZE: usize = 1101;
 const INPUT_USAGE_BUF_SIZE: usize = 10;
 const READ_INTERRUPT_TIMEOUT: Duration = Duration::from_millis(1000);
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(20);
 const EVENT_DEVICE_NAME: &str = "  Parblo Intangbo  S(F7)";
 const VIRTUAL_DIGITIZER_NAME: &str = "Parblo Intangbo S (Digitizer)";
 const VIRTUAL_KEYBOARD_NAME: &str = "Parblo Intangbo S (Keyboard)";
+const VIRTUAL_CONSUMER_CONTROL_NAME: &str = "Parblo Intangbo S (Consumer Control)";
+const VIRTUAL_POINTER_NAME: &str = "Parblo Intangbo S (Mouse)";
+const VIRTUAL_ABSOLUTE_POINTER_NAME: &str = "Parblo Intangbo S (Absolute Mouse)";
+const VIRTUAL_PAD_NAME: &str = "Parblo Intangbo S Pad";
+const PAD_RING_MAX_VALUE: i32 = 71;
 const DEVICE_HANDSHAKE_DATA_LIST: &[&[u8]] = &[
     &[
         0xfd, 0x89, 0xff, 0xff, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x03, 0x01, 0x01, 0x01, 0x91,
@@ -45,12 +59,56 @@ pub struct DriverTask {
     cancel_token: CancelToken,
     digitizer_uinput: UInputDevice,
     keyboard_uinput: UInputDevice,
+    keyboard_codes: BTreeSet<EV_KEY>, // 虚拟键盘启用的按键码
+    consumer_uinput: UInputDevice,
+    consumer_codes: BTreeSet<EV_KEY>, // 虚拟多媒体按键设备启用的按键码
+    pointer_uinput: UInputDevice,
+    pad_uinput: Option<UInputDevice>, // 只有某个方案使用了pad时才创建虚拟按键面板
     handle: UsbDeviceHandle<rusb::GlobalContext>,
     conf: Config,
     latest_conf: Arc<Mutex<Option<Config>>>,
     keymap_index: usize,
-    pressed_keys: HashSet<EV_KEY>, // 设备本身不支持同时按下多个键，因此可直接用集合记录某个键的按键码组合
+    pressed_keys: HashMap<KeySource, PressedKeys>, // 绘图板本身不支持同时按下多个键，但笔侧键可以与其同时按下，因此按来源记录按键码组合
+    latched_keys: Option<LatchedKeys>,             // latch:锁定的按键
+    toggled_keys: Vec<Arc<Vec<EV_KEY>>>,           // toggle:按下的按键组合
+    pressed_buttons: HashMap<KeySource, EV_KEY>,   // 跟随物理按键按下的虚拟鼠标按键
+    scheduled_keys: KeyQueue,                      // 等待上报的按键与鼠标按键事件
+    macro_until: Duration, // 正在执行的宏的最后一帧的上报时间，在此之前再次触发宏会被忽略
     stylus: StylusStatus,
+    relative: RelativeStatus,
+    scroll: ScrollStatus,
+    ring: RingStatus,
+    pad: PadStatus,
+    gesture: Option<GestureStatus>,
+    sequence: Option<SequenceStatus>,
+    pen_mode: PenMode,
+    mapping: MappingStatus,
+    filter: Option<PositionFilter>,
+    stabilizer: Option<Stabilizer>,
+    stabilizer_enabled: bool, // 运行时切换的稳定器开关；笔画进行中关闭时，笔画结束后才真正关闭
+    event_time: Duration,     // 当前USB传输完成时的CLOCK_MONOTONIC时间
+}
+struct PressedKeys {
+    codes: Vec<EV_KEY>,
+    release_after: Duration, // 满足最短按下时间后才能释放
+    delay: Duration,
+}
+struct LatchedKeys {
+    codes: Vec<EV_KEY>,
+    armed: bool, // 下一次按键或笔画已经开始，结束时释放
+}
+// 按键事件上报到的虚拟设备
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum KeyDevice {
+    Keyboard,
+    Consumer,
+    Pointer,
+}
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum KeySource {
+    Pad,
+    StylusButton0,
+    StylusButton1,
 }
 struct StylusStatus {
     in_area: bool,
@@ -58,19 +116,68 @@ struct StylusStatus {
     pressure: u16,
     button0_pressed: bool,
     button1_pressed: bool,
-    x: u16,
-    y: u16,
+    button0_passthrough: bool, // 是否向虚拟绘图板上报了按下BTN_STYLUS
+    button1_passthrough: bool, // 是否向虚拟绘图板上报了按下BTN_STYLUS2
+    x: i32,
+    y: i32,
     tilt_x: i8,
     tilt_y: i8,
 }
+struct RelativeStatus {
+    last_position: Option<(u16, u16)>, // 上一次的设备坐标，为None时表示笔尖不在感应区域内
+    last_time: Duration,
+    remainder: (f32, f32), // 尚未上报的不足1的位移
+    tip_pressed: bool,
+}
+struct ScrollStatus {
+    held: Option<KeySource>,           // 按住的滚动键，为None时表示不在滚动
+    last_position: Option<(u16, u16)>, // 上一次的设备坐标，为None时表示尚未开始滚动
+    remainder: (f32, f32),             // 尚未上报的不足1的高精度滚动量
+    detent: (i32, i32),                // 已上报但不足滚轮一格的高精度滚动量
+}
+struct RingStatus {
+    last_code: u16, // 上一次转动转环的事件码，转动方向改变时不加速
+    last_time: Option<Duration>,
+}
+struct PadStatus {
+    pressed: Option<EV_KEY>, // 虚拟绘图板按键面板上按下的按钮
+    ring: i32,               // 虚拟转环的当前位置
+}
+// 正在识别短按、长按与双击的按钮
+struct GestureStatus {
+    code: EV_KEY, // 按钮对应的虚拟按键面板按键码，用于区分不同的按钮
+    gesture: Arc<Gesture>,
+    phase: GesturePhase,
+    deadline: Option<Duration>, // 计时器到期的时间
+}
+enum GesturePhase {
+    Pressed,  // 按下后尚未达到长按时间
+    Released, // 短按后等待再次按下
+    Active,   // 已经触发按键映射，松开按钮时释放
+}
+// 正在输入的按钮序列
+struct SequenceStatus {
+    buttons: Vec<EV_KEY>,
+    keymaps: Vec<Keymap>, // 序列没有完成时依次执行已按下的按钮自身的按键映射
+    held: bool,           // 最后按下的按钮是否仍然按住
+    deadline: Duration,
+}
+struct MappingStatus {
+    area_index: usize, // 0对应顶层配置的映射区域，其余对应conf.areas[area_index - 1]
+    x_map: Option<(f32, f32)>,
+    y_map: Option<(f32, f32)>,
+    precision: bool,                   // 是否处于精确模式
+    precision_held: Option<KeySource>, // 精确模式是否由按住按键触发，若是则在释放该按键时退出
+}
 impl DriverTask {
     pub fn new(
         cancel_token: CancelToken,
         mut conf: Config,
         watch_config_change_task: Option<&mut WatchConfigChangeTask>,
     ) -> Result<Self> {
-        let (digitizer_uinput, keyboard_uinput) =
-            create_uinput_device(&mut conf).context("无法创建虚拟设备")?;
+        let keyboard_codes = conf.keyboard_codes();
+        let consumer_codes = conf.consumer_codes();
+        let devices = create_uinput_device(&mut conf).context("无法创建虚拟设备")?;
         let handle = open_usb_device_handle().context("无法打开USB设备句柄")?;
 
         let latest_conf = Arc::new(Mutex::new(None));
@@ -81,26 +188,77 @@ impl DriverTask {
             });
         }
 
+        let mapping = MappingStatus {
+            area_index: 0,
+            x_map: conf.x_map,
+            y_map: conf.y_map,
+            precision: false,
+            precision_held: None,
+        };
+        let filter = conf.filter.map(PositionFilter::new);
+        let pen_mode = conf.pen_mode;
+        let stabilizer_enabled = conf.stabilizer.enabled;
+        let stabilizer = stabilizer_enabled.then(|| Stabilizer::new(conf.stabilizer.length));
         Ok(Self {
             cancel_token,
-            digitizer_uinput,
-            keyboard_uinput,
+            digitizer_uinput: devices.digitizer,
+            keyboard_uinput: devices.keyboard,
+            keyboard_codes,
+            consumer_uinput: devices.consumer,
+            consumer_codes,
+            pointer_uinput: devices.pointer,
+            pad_uinput: devices.pad,
             handle,
             conf,
             latest_conf,
             keymap_index: 0,
-            pressed_keys: HashSet::new(),
+            pressed_keys: HashMap::new(),
+            latched_keys: None,
+            toggled_keys: vec![],
+            pressed_buttons: HashMap::new(),
+            scheduled_keys: KeyQueue::default(),
+            macro_until: Duration::ZERO,
             stylus: StylusStatus {
                 in_area: false,
                 tip_pressed: false,
                 pressure: 0,
                 button0_pressed: false,
                 button1_pressed: false,
+                button0_passthrough: false,
+                button1_passthrough: false,
                 x: 0,
                 y: 0,
                 tilt_x: 0,
                 tilt_y: 0,
             },
+            relative: RelativeStatus {
+                last_position: None,
+                last_time: Duration::ZERO,
+                remainder: (0f32, 0f32),
+                tip_pressed: false,
+            },
+            scroll: ScrollStatus {
+                held: None,
+                last_position: None,
+                remainder: (0f32, 0f32),
+                detent: (0, 0),
+            },
+            ring: RingStatus {
+                last_code: 0,
+                last_time: None,
+            },
+            pad: PadStatus {
+                pressed: None,
+                ring: 0,
+            },
+            gesture: None,
+            sequence: None,
+            pen_mode,
+            mapping,
+            filter,
+            stabilizer,
+            stabilizer_enabled,
+            event_time: Duration::ZERO,
         })
     }
 
@@ -108,22 +266,52 @@ impl DriverTask {
         info!("驱动任务开始运行");
         loop {
             if self.cancel_token.cancelled() {
-                return Ok(());
+                // 退出前上报所有等待中的按键事件
+                self.release_sticky_keys()?;
+                return self.flush_scheduled_keys(true);
             }
-            self.check_config_change();
+            self.check_config_change()?;
             self.read_and_handle_device_input()?;
         }
     }
 
-    fn check_config_change(&mut self) {
+    fn check_config_change(&mut self) -> Result<()> {
         let mut latest_conf = match self.latest_conf.lock().take() {
             Some(keymaps) => keymaps,
-            None => return,
+            None => return Ok(()),
         };
         {
             // 修正不支持热更的字段
             latest_conf.x_max_value = self.conf.x_max_value;
             latest_conf.y_max_value = self.conf.y_max_value;
+            latest_conf.axis_scale = self.conf.axis_scale;
+            latest_conf.digitizer_type = self.conf.digitizer_type;
+        }
+        // 先创建新的虚拟按键面板，虚拟键盘也重新创建成功后才替换原先的设备
+        let pad_uinput = match (latest_conf.uses_pad(), &self.pad_uinput) {
+            (true, None) => match open_evdev().and_then(|evdev| create_pad_uinput_device(&evdev)) {
+                Ok(pad_uinput) => Some(Some(pad_uinput)),
+                Err(e) => {
+                    warn!("无法创建虚拟按键面板，忽略本次配置文件的变动: {:?}", e);
+                    return Ok(());
+                }
+            },
+            (false, Some(_)) => Some(None),
+            _ => None,
+        };
+        let keyboard_codes = latest_conf.keyboard_codes();
+        let consumer_codes = latest_conf.consumer_codes();
+        if !keyboard_codes.is_subset(&self.keyboard_codes)
+            || !consumer_codes.is_subset(&self.consumer_codes)
+        {
+            // 虚拟设备创建失败时保留原先的配置与虚拟设备
+            if let Err(e) = self.recreate_keyboard(keyboard_codes, consumer_codes) {
+                warn!("无法重新创建虚拟键盘，忽略本次配置文件的变动: {:?}", e);
+                return Ok(());
+            }
+        }
+        if let Some(pad_uinput) = pad_uinput {
+            self.replace_pad(pad_uinput)?;
         }
         if latest_conf.keymaps.len() >= self.conf.keymaps.len() {
             info!(
@@ -133,17 +321,43 @@ impl DriverTask {
         } else {
             info!("已重新加载配置文件；切换到按键映射方案0");
             self.keymap_index = 0;
+            self.release_sticky_keys()?;
         }
+        let prev_pen_mode = self.conf.pen_mode;
+        let prev_stabilizer_enabled = self.conf.stabilizer.enabled;
         self.conf = latest_conf;
+        self.filter = self.conf.filter.map(PositionFilter::new);
+        // 只有配置文件中的开关变化时才覆盖运行时切换的状态；稳定器保留虚拟笔尖的位置，避免笔画中途跳动
+        if let Some(stabilizer) = &mut self.stabilizer {
+            stabilizer.set_length(self.conf.stabilizer.length);
+        }
+        if self.conf.stabilizer.enabled != prev_stabilizer_enabled {
+            self.set_stabilizer_enabled(self.conf.stabilizer.enabled);
+        }
+        if self.mapping.area_index > self.conf.areas.len() {
+            self.mapping.area_index = 0;
+        }
+        self.apply_area();
+        if self.conf.pen_mode != prev_pen_mode {
+            self.set_pen_mode(self.conf.pen_mode, None)?;
+        }
+        Ok(())
     }
 
     fn read_and_handle_device_input(&mut self) -> Result<()> {
         let mut buf = [0u8; INPUT_USAGE_BUF_SIZE];
         loop {
-            match self
-                .handle
-                .read_interrupt(IN_ENDPOINT, &mut buf, READ_INTERRUPT_TIMEOUT)
-            {
+            // 有等待上报的按键事件或者计时器时，最多等待到该事件的上报时间或计时器到期
+            let timeout = match self.next_timer_timeout()? {
+                Some(timeout) => timeout.clamp(Duration::from_millis(1), READ_INTERRUPT_TIMEOUT),
+                None => READ_INTERRUPT_TIMEOUT,
+            };
+            let result = self.handle.read_interrupt(IN_ENDPOINT, &mut buf, timeout);
+            self.event_time = monotonic_time()?;
+            self.flush_scheduled_keys(false)?;
+            self.check_gesture_timer()?;
+            self.check_sequence_timer()?;
+            match result {
                 Ok(len) => {
                     self.handle_device_input(&buf[..len])?;
                 }
@@ -180,74 +394,62 @@ impl DriverTask {
     fn handle_button_event(&mut self, buf: &[u8]) -> Result<()> {
         let code = ((buf[1] as u16) << 8) | (buf[2] as u16);
         macro_rules! handle {
-            ($desc:literal, $field:ident) => {
+            ($desc:literal, $field:ident, $pad_code:ident) => {
                 debug!($desc);
-                match &self
+                let keymap = self
                     .conf
                     .keymaps
                     .get(self.keymap_index)
                     .context("按键映射方案下标越界")?
                     .$field
-                {
-                    Keymap::Press(codes) => {
-                        for code in codes.iter() {
-                            debug!("虚拟键盘 - 按下{:?}", code);
-                            self.write_keyboard_event(EventCode::EV_KEY(*code), 1)?;
-                            self.pressed_keys.insert(*code);
-                        }
-                        self.write_keyboard_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
-                    }
-                    Keymap::SwitchSchema => {
-                        self.switch_schema();
-                    }
-                    _ => {}
-                }
+                    .clone();
+                self.press_pad_sequence(keymap, EV_KEY::$pad_code)?;
             };
         }
         match code {
             0x0000 => {
                 debug!("收到释放按键事件");
-                if !self.pressed_keys.is_empty() {
-                    for code in self.pressed_keys.iter() {
-                        debug!("虚拟键盘 - 释放{:?}", code);
-                        self.write_keyboard_event(EventCode::EV_KEY(*code), 0)?;
-                    }
-                    self.pressed_keys.clear();
-                    self.write_keyboard_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+                if let Some(sequence) = &mut self.sequence {
+                    sequence.held = false;
                 }
+                self.release_pad_keymap()?;
             }
             0x0100 => {
-                handle!("收到按下按钮0事件", button0);
+                handle!("收到按下按钮0事件", button0, BTN_0);
             }
             0x0200 => {
-                handle!("收到按下按钮1事件", button1);
+                handle!("收到按下按钮1事件", button1, BTN_1);
             }
             0x0400 => {
-                handle!("收到按下按钮2事件", button2);
+                handle!("收到按下按钮2事件", button2, BTN_2);
             }
             0x0800 => {
-                handle!("收到按下按钮3事件", button3);
+                handle!("收到按下按钮3事件", button3, BTN_3);
             }
             0x0801 => {
-                handle!("收到顺时针转动转环事件", ring1);
+                debug!("收到顺时针转动转环事件");
+                self.flush_pad_input()?;
+                self.handle_ring_event(code)?;
             }
             0x0802 => {
-                handle!("收到逆时针转动转环事件", ring0);
+                debug!("收到逆时针转动转环事件");
+                self.flush_pad_input()?;
+                self.handle_ring_event(code)?;
             }
             0x0803 => {
-                handle!("收到按下转环中心按钮事件", ring_button);
+                handle!("收到按下转环中心按钮事件", ring_button, BTN_8);
             }
             0x1000 => {
-                handle!("收到按下按钮4事件", button4);
+                handle!("收到按下按钮4事件", button4, BTN_4);
             }
             0x2000 => {
-                handle!("收到按下按钮5事件", button5);
+                handle!("收到按下按钮5事件", button5, BTN_5);
             }
             0x4000 => {
-                handle!("收到按下按钮6事件", button6);
+                handle!("收到按下按钮6事件", button6, BTN_6);
             }
             0x8000 => {
-                handle!("收到按下按钮7事件", button7);
+                handle!("收到按下按钮7事件", button7, BTN_7);
             }
             _ => {
                 warn!("收到了未知的按键事件：{:02x?}", buf);
@@ -256,7 +458,542 @@ impl DriverTask {
         Ok(())
     }
 
-    fn switch_schema(&mut self) {
+    fn handle_ring_event(&mut self, code: u16) -> Result<()> {
+        let keymap = self.current_keymap().context("按键映射方案下标越界")?;
+        let acceleration = keymap.ring_acceleration;
+        let keymap = match code {
+            0x0801 => keymap.ring1.clone(),
+            _ => keymap.ring0.clone(),
+        };
+
+        // 根据相邻两格转动的间隔计算加速倍数
+        let last_time = self.ring.last_time.replace(self.event_time);
+        let last_code = std::mem::replace(&mut self.ring.last_code, code);
+        let multiplier = match (acceleration, last_time) {
+            (Some(acceleration), Some(last_time)) if last_code == code => {
+                acceleration.multiplier(self.event_time.saturating_sub(last_time))
+            }
+            _ => 1f32,
+        };
+        if multiplier > 1f32 {
+            debug!("转环加速：倍数({:.2})", multiplier);
+        }
+
+        match keymap {
+            Keymap::Pad => match code {
+                0x0801 => self.write_pad_ring(1),
+                _ => self.write_pad_ring(-1),
+            },
+            Keymap::Wheel(mut wheel) => {
+                wheel.amount = (wheel.amount as f32 * multiplier).round() as i32;
+                self.press_keymap(Keymap::Wheel(wheel), KeySource::Pad)
+            }
+            Keymap::Press(_) => {
+                // 额外的次数立即按下并释放，最后一次保持按下直到收到释放按键事件
+                for _ in 1..multiplier.round() as u32 {
+                    self.press_keymap(keymap.clone(), KeySource::Pad)?;
+                    self.release_keymap(KeySource::Pad)?;
+                }
+                self.press_keymap(keymap, KeySource::Pad)
+            }
+            _ => self.press_keymap(keymap, KeySource::Pad),
+        }
+    }
+
+    // 按下的按钮可能是某个序列的一部分：序列完成时触发序列的按键映射，否则等待下一个按钮
+    fn press_pad_sequence(&mut self, keymap: Keymap, code: EV_KEY) -> Result<()> {
+        let mut buttons = match &self.sequence {
+            Some(sequence) => sequence.buttons.clone(),
+            None => vec![],
+        };
+        buttons.push(code);
+        if let Some(sequence) = self.conf.sequences.iter().find(|x| x.buttons == buttons) {
+            debug!("按键面板 - 触发序列{:?}", buttons);
+            let action = sequence.action.clone();
+            self.sequence = None;
+            self.flush_gesture()?;
+            return self.press_keymap(action, KeySource::Pad);
+        }
+        let timeout = self
+            .conf
+            .sequences
+            .iter()
+            .filter(|x| x.buttons.starts_with(&buttons))
+            .map(|x| x.timeout)
+            .max();
+        if let Some(timeout) = timeout {
+            debug!("按键面板 - 等待序列{:?}的下一个按钮", buttons);
+            let mut keymaps = match self.sequence.take() {
+                Some(sequence) => sequence.keymaps,
+                None => vec![],
+            };
+            keymaps.push(keymap);
+            self.sequence = Some(SequenceStatus {
+                buttons,
+                keymaps,
+                held: true,
+                deadline: self.event_time + timeout,
+            });
+            return Ok(());
+        }
+        // 序列中断：先依次执行已按下的按钮自身的按键映射，再重新处理本次按下的按钮
+        if self.sequence.is_some() {
+            self.flush_sequence()?;
+            return self.press_pad_sequence(keymap, code);
+        }
+        self.press_pad_keymap(keymap, code)
+    }
+
+    // 序列没有完成时依次执行已按下的按钮自身的按键映射；若最后一个按钮仍然按住，则松开按钮时才释放
+    fn flush_sequence(&mut self) -> Result<()> {
+        let Some(sequence) = self.sequence.take() else {
+            return Ok(());
+        };
+        debug!("按键面板 - 序列{:?}没有完成", sequence.buttons);
+        let count = sequence.buttons.len();
+        for (i, (keymap, code)) in sequence
+            .keymaps
+            .into_iter()
+            .zip(sequence.buttons)
+            .enumerate()
+        {
+            self.press_pad_keymap(keymap, code)?;
+            if i + 1 < count || !sequence.held {
+                self.release_pad_keymap()?;
+            }
+        }
+        Ok(())
+    }
+
+    // 转动转环或者开始笔画时，不再等待序列的下一个按钮或者双击
+    fn flush_pad_input(&mut self) -> Result<()> {
+        self.flush_sequence()?;
+        self.flush_gesture()
+    }
+
+    fn check_sequence_timer(&mut self) -> Result<()> {
+        match &self.sequence {
+            Some(sequence) if sequence.deadline <= self.event_time => self.flush_sequence(),
+            _ => Ok(()),
+        }
+    }
+
+    fn press_pad_keymap(&mut self, keymap: Keymap, code: EV_KEY) -> Result<()> {
+        match keymap {
+            Keymap::Pad => {
+                self.flush_gesture()?;
+                self.press_pad_button(code)
+            }
+            Keymap::Gesture(gesture) => self.press_gesture(gesture, code),
+            _ => {
+                self.flush_gesture()?;
+                self.press_keymap(keymap, KeySource::Pad)
+            }
+        }
+    }
+
+    fn release_pad_keymap(&mut self) -> Result<()> {
+        self.release_gesture()?;
+        self.release_keymap(KeySource::Pad)
+    }
+
+    fn press_gesture(&mut self, gesture: Arc<Gesture>, code: EV_KEY) -> Result<()> {
+        if let Some(status) = &mut self.gesture
+            && status.code == code
+            && let GesturePhase::Released = status.phase
+        {
+            debug!("按键面板 - 双击");
+            status.phase = GesturePhase::Active;
+            status.deadline = None;
+            let keymap = status.gesture.double_tap.clone();
+            return self.press_keymap(keymap, KeySource::Pad);
+        }
+        self.flush_gesture()?;
+        let (phase, deadline) = match (&gesture.hold, &gesture.double_tap) {
+            // 只配置了短按时立即触发
+            (Keymap::None, Keymap::None) => {
+                self.press_keymap(gesture.tap.clone(), KeySource::Pad)?;
+                (GesturePhase::Active, None)
+            }
+            (Keymap::None, _) => (GesturePhase::Pressed, None),
+            _ => (
+                GesturePhase::Pressed,
+                Some(self.event_time + gesture.hold_threshold),
+            ),
+        };
+        self.gesture = Some(GestureStatus {
+            code,
+            gesture,
+            phase,
+            deadline,
+        });
+        Ok(())
+    }
+
+    fn release_gesture(&mut self) -> Result<()> {
+        let Some(status) = &mut self.gesture else {
+            return Ok(());
+        };
+        match status.phase {
+            GesturePhase::Pressed => {
+                // 没有配置双击时立即触发短按，随后由release_keymap释放
+                if let Keymap::None = status.gesture.double_tap {
+                    debug!("按键面板 - 短按");
+                    let keymap = status.gesture.tap.clone();
+                    self.gesture = None;
+                    return self.press_keymap(keymap, KeySource::Pad);
+                }
+                status.phase = GesturePhase::Released;
+                status.deadline = Some(self.event_time + status.gesture.double_tap_interval);
+            }
+            GesturePhase::Released => {}
+            GesturePhase::Active => {
+                self.gesture = None;
+            }
+        }
+        Ok(())
+    }
+
+    // 计时器到期时，按住的按钮触发长按，等待再次按下的按钮触发短按
+    fn check_gesture_timer(&mut self) -> Result<()> {
+        let Some(status) = &mut self.gesture else {
+            return Ok(());
+        };
+        if status.deadline.is_none_or(|x| x > self.event_time) {
+            return Ok(());
+        }
+        match status.phase {
+            GesturePhase::Pressed => {
+                debug!("按键面板 - 长按");
+                status.phase = GesturePhase::Active;
+                status.deadline = None;
+                let keymap = status.gesture.hold.clone();
+                self.press_keymap(keymap, KeySource::Pad)
+            }
+            _ => self.flush_gesture(),
+        }
+    }
+
+    // 有其他输入时不再等待双击，立即触发短按
+    fn flush_gesture(&mut self) -> Result<()> {
+        let Some(GestureStatus {
+            phase: GesturePhase::Released,
+            ..
+        }) = self.gesture
+        else {
+            return Ok(());
+        };
+        let status = self.gesture.take().unwrap();
+        debug!("按键面板 - 短按");
+        self.press_keymap(status.gesture.tap.clone(), KeySource::Pad)?;
+        self.release_keymap(KeySource::Pad)
+    }
+
+    fn press_keymap(&mut self, keymap: Keymap, source: KeySource) -> Result<()> {
+        // 锁定的按键作用于下一次按键
+        if !matches!(keymap, Keymap::Latch(_) | Keymap::Toggle(_) | Keymap::None)
+            && let Some(latched) = &mut self.latched_keys
+        {
+            latched.armed = true;
+        }
+        match keymap {
+            Keymap::Press(combo) => {
+                debug!("虚拟键盘 - 按下{:?}", combo.codes);
+                let time = self.press_keys(&combo.codes, combo.delay);
+                let pressed = self.pressed_keys.entry(source).or_insert(PressedKeys {
+                    codes: vec![],
+                    release_after: Duration::ZERO,
+                    delay: Duration::ZERO,
+                });
+                for code in &combo.codes {
+                    if !pressed.codes.contains(code) {
+                        pressed.codes.push(*code);
+                    }
+                }
+                pressed.release_after = pressed.release_after.max(time + combo.hold);
+                pressed.delay = pressed.delay.max(combo.delay);
+                self.flush_scheduled_keys(false)?;
+            }
+            Keymap::Latch(codes) => {
+                // 再次触发时取消锁定
+                let same = self
+                    .latched_keys
+                    .as_ref()
+                    .is_some_and(|latched| latched.codes == *codes);
+                self.release_latched_keys();
+                if !same {
+                    debug!("虚拟键盘 - 锁定{:?}", codes);
+                    self.press_keys(&codes, Duration::ZERO);
+                    self.latched_keys = Some(LatchedKeys {
+                        codes: codes.to_vec(),
+                        armed: false,
+                    });
+                }
+                self.flush_scheduled_keys(false)?;
+            }
+            Keymap::Toggle(codes) => {
+                match self.toggled_keys.iter().position(|x| *x == codes) {
+                    Some(index) => {
+                        debug!("虚拟键盘 - 释放切换的{:?}", codes);
+                        self.toggled_keys.remove(index);
+                        self.release_keys(&codes, self.event_time, Duration::ZERO);
+                    }
+                    None => {
+                        debug!("虚拟键盘 - 切换为按下{:?}", codes);
+                        self.press_keys(&codes, Duration::ZERO);
+                        self.toggled_keys.push(codes);
+                    }
+                }
+                self.flush_scheduled_keys(false)?;
+            }
+            Keymap::Macro(steps) => {
+                if self.macro_until > self.event_time {
+                    debug!("虚拟键盘 - 宏正在执行，忽略本次触发");
+                    return Ok(());
+                }
+                self.macro_until = self.scheduled_keys.schedule_macro(self.event_time, &steps);
+                self.flush_scheduled_keys(false)?;
+            }
+            Keymap::Exec(argv) => {
+                // 运行命令失败不影响驱动
+                if let Some(conf) = &self.conf.exec
+                    && let Err(e) = exec::spawn_command(conf, &argv)
+                {
+                    warn!("{:?}", e);
+                }
+            }
+            Keymap::SwitchSchema => {
+                self.switch_schema()?;
+            }
+            Keymap::CycleArea => {
+                self.cycle_area();
+            }
+            Keymap::PrecisionMode => {
+                // 已经通过togglePrecisionMode进入精确模式时，松开按键不退出
+                if !self.mapping.precision {
+                    self.enter_precision_mode();
+                    self.mapping.precision_held = Some(source);
+                }
+            }
+            Keymap::TogglePrecisionMode => {
+                if self.mapping.precision {
+                    self.exit_precision_mode();
+                } else {
+                    self.enter_precision_mode();
+                }
+            }
+            Keymap::ToggleStabilizer => {
+                self.toggle_stabilizer();
+            }
+            Keymap::TogglePenMode => {
+                let mode = match self.pen_mode {
+                    PenMode::Absolute => PenMode::Relative,
+                    PenMode::Relative => PenMode::Absolute,
+                };
+                self.set_pen_mode(mode, Some(source))?;
+            }
+            Keymap::Scroll => {
+                debug!("虚拟鼠标 - 开始滚动");
+                self.scroll.held = Some(source);
+                self.scroll.last_position = None;
+            }
+            Keymap::Wheel(wheel) => {
+                let (dx, dy) = match wheel.horizontal {
+                    true => (wheel.amount, 0),
+                    false => (0, wheel.amount),
+                };
+                debug!("虚拟鼠标 - 上报滚动({},{})", dx, dy);
+                self.write_pointer_wheel(dx, dy)?;
+                self.write_pointer_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+            }
+            Keymap::Click(button) => {
+                let code = button.code();
+                debug!("虚拟鼠标 - 按下{:?}", code);
+                if let Some(code) = self.pressed_buttons.insert(source, code) {
+                    self.write_pointer_event(EventCode::EV_KEY(code), 0)?;
+                }
+                self.write_pointer_event(EventCode::EV_KEY(code), 1)?;
+                self.write_pointer_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+            }
+            Keymap::DoubleClick(button) => {
+                let code = button.code();
+                debug!("虚拟鼠标 - 双击{:?}", code);
+                // 两次点击的各个事件间隔一段时间上报，同一时刻的点击可能不会被识别为双击
+                let frames = [1, 0, 1, 0].map(|value| vec![(code, value)]);
+                self.scheduled_keys
+                    .schedule(self.event_time, frames, DOUBLE_CLICK_INTERVAL);
+                self.flush_scheduled_keys(false)?;
+            }
+            // 绘图板按键面板的按钮与转环由handle_button_event直接处理
+            Keymap::Passthrough | Keymap::Pad | Keymap::Gesture(_) | Keymap::None => {}
+        }
+        Ok(())
+    }
+
+    fn release_keymap(&mut self, source: KeySource) -> Result<()> {
+        if let (KeySource::Pad, Some(code)) = (source, self.pad.pressed) {
+            self.release_pad_button(code)?;
+        }
+        if self.mapping.precision_held == Some(source) {
+            self.exit_precision_mode();
+        }
+        if let Some(code) = self.pressed_buttons.remove(&source) {
+            debug!("虚拟鼠标 - 释放{:?}", code);
+            self.write_pointer_event(EventCode::EV_KEY(code), 0)?;
+            self.write_pointer_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+        }
+        if self.scroll.held == Some(source) {
+            debug!("虚拟鼠标 - 结束滚动");
+            self.scroll.held = None;
+            self.scroll.last_position = None;
+        }
+        if let Some(pressed) = self.pressed_keys.remove(&source)
+            && !pressed.codes.is_empty()
+        {
+            debug!("虚拟键盘 - 释放{:?}", pressed.codes);
+            self.release_keys(&pressed.codes, pressed.release_after, pressed.delay);
+        }
+        if self
+            .latched_keys
+            .as_ref()
+            .is_some_and(|latched| latched.armed)
+        {
+            self.release_latched_keys();
+        }
+        self.flush_scheduled_keys(false)
+    }
+
+    // 先按下修饰键，再按下其他按键，两帧之间间隔delay；返回最后一帧的上报时间
+    fn press_keys(&mut self, codes: &[EV_KEY], delay: Duration) -> Duration {
+        let (modifiers, keys): (Vec<_>, Vec<_>) = codes
+            .iter()
+            .cloned()
+            .partition(|code| binding::is_modifier(*code));
+        self.scheduled_keys.schedule(
+            self.event_time,
+            [modifiers, keys].map(|codes| codes.into_iter().map(|code| (code, 1)).collect()),
+            delay,
+        )
+    }
+
+    // 按相反的顺序释放，先释放其他按键，再释放修饰键；其他来源仍然按住的键在上报时过滤
+    fn release_keys(&mut self, codes: &[EV_KEY], time: Duration, delay: Duration) {
+        let (modifiers, keys): (Vec<_>, Vec<_>) = codes
+            .iter()
+            .rev()
+            .cloned()
+            .partition(|code| binding::is_modifier(*code));
+        self.scheduled_keys.schedule(
+            time,
+            [keys, modifiers].map(|codes| codes.into_iter().map(|code| (code, 0)).collect()),
+            delay,
+        );
+    }
+
+    fn release_latched_keys(&mut self) {
+        if let Some(latched) = self.latched_keys.take() {
+            debug!("虚拟键盘 - 解除锁定{:?}", latched.codes);
+            self.release_keys(&latched.codes, self.event_time, Duration::ZERO);
+        }
+    }
+
+    // 笔画开始时锁定的按键作用于这次笔画，笔画结束时释放
+    fn latch_stroke(&mut self, touching: bool) {
+        match &mut self.latched_keys {
+            Some(latched) if touching => latched.armed = true,
+            Some(latched) if latched.armed => self.release_latched_keys(),
+            _ => {}
+        }
+    }
+
+    // 切换按键映射方案或退出时释放latch:与toggle:按下的按键
+    fn release_sticky_keys(&mut self) -> Result<()> {
+        self.release_latched_keys();
+        for codes in std::mem::take(&mut self.toggled_keys) {
+            debug!("虚拟键盘 - 释放切换的{:?}", codes);
+            self.release_keys(&codes, self.event_time, Duration::ZERO);
+        }
+        self.flush_scheduled_keys(false)
+    }
+
+    // 上报已经到时间的按键事件；all为true时不论时间上报所有事件
+    fn flush_scheduled_keys(&mut self, all: bool) -> Result<()> {
+        let now = match all {
+            true => None,
+            false => Some(monotonic_time()?),
+        };
+        // 在上报时才判断按键是否仍被按住，宏执行期间锁定或释放的按键也能得到正确的处理
+        while let Some(frame) = self.scheduled_keys.pop_frame(now, |code| {
+            is_key_held(
+                &self.pressed_keys,
+                &self.latched_keys,
+                &self.toggled_keys,
+                code,
+            )
+        }) {
+            let mut devices = vec![];
+            for (code, value) in frame {
+                devices.push(self.write_key_event(code, value)?);
+            }
+            self.write_key_sync(&devices)?;
+        }
+        Ok(())
+    }
+
+    // 距离下一个等待上报的按键事件、手势或序列的计时器到期的时间
+    fn next_timer_timeout(&self) -> Result<Option<Duration>> {
+        let deadline = [
+            self.scheduled_keys.next_time(),
+            self.gesture.as_ref().and_then(|x| x.deadline),
+            self.sequence.as_ref().map(|x| x.deadline),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        match deadline {
+            Some(deadline) => Ok(Some(deadline.saturating_sub(monotonic_time()?))),
+            None => Ok(None),
+        }
+    }
+
+    fn press_pad_button(&mut self, code: EV_KEY) -> Result<()> {
+        debug!("虚拟按键面板 - 按下{:?}", code);
+        self.pad.pressed = Some(code);
+        self.write_pad_event(EventCode::EV_KEY(code), 1)?;
+        self.write_pad_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)
+    }
+
+    fn release_pad_button(&mut self, code: EV_KEY) -> Result<()> {
+        debug!("虚拟按键面板 - 释放{:?}", code);
+        self.pad.pressed = None;
+        self.write_pad_event(EventCode::EV_KEY(code), 0)?;
+        self.write_pad_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)
+    }
+
+    // 转环位置在[1, PAD_RING_MAX_VALUE]之间循环；libinput把0视为手指离开转环，因此不上报0
+    fn write_pad_ring(&mut self, delta: i32) -> Result<()> {
+        let ring = (self.pad.ring - 1 + delta).rem_euclid(PAD_RING_MAX_VALUE) + 1;
+        debug!("虚拟按键面板 - 转环位置({})", ring);
+        self.pad.ring = ring;
+        self.write_pad_event(EventCode::EV_ABS(EV_ABS::ABS_WHEEL), ring)?;
+        self.write_pad_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)
+    }
+
+    fn write_pad_event(&self, code: EventCode, value: i32) -> Result<()> {
+        // 没有方案使用pad时不会创建虚拟按键面板，也不会有需要上报的事件
+        let Some(pad_uinput) = &self.pad_uinput else {
+            return Ok(());
+        };
+        pad_uinput
+            .write_event(&InputEvent::new(&self.event_timeval(), &code, value))
+            .context("UInputDevice::write_event(pad)")
+    }
+
+    fn current_keymap(&self) -> Option<&KeymapConfig> {
+        self.conf.keymaps.get(self.keymap_index)
+    }
+
+    fn switch_schema(&mut self) -> Result<()> {
         let len = self.conf.keymaps.len();
         let current_index = self.keymap_index;
         let new_index = (current_index + 1) % len;
@@ -264,15 +1001,219 @@ impl DriverTask {
             self.keymap_index = new_index;
             info!("已切换到按键映射方案{}", new_index);
         }
+        self.release_sticky_keys()
+    }
+
+    fn cycle_area(&mut self) {
+        let len = self.conf.areas.len() + 1;
+        self.mapping.area_index = (self.mapping.area_index + 1) % len;
+        self.apply_area();
+        match self.mapping.area_index {
+            0 => {
+                info!("已切换到默认映射区域");
+            }
+            i => {
+                info!("已切换到映射区域{}({})", i, self.conf.areas[i - 1].name);
+            }
+        }
+    }
+
+    fn apply_area(&mut self) {
+        let (x_map, y_map) = match self.mapping.area_index {
+            0 => (self.conf.x_map, self.conf.y_map),
+            i => (self.conf.areas[i - 1].x_map, self.conf.areas[i - 1].y_map),
+        };
+        self.mapping.x_map = x_map;
+        self.mapping.y_map = y_map;
+        self.mapping.precision = false;
+        self.mapping.precision_held = None;
+    }
+
+    // source为触发切换的按键；若为笔侧键，则切换后仍视为按下，直到松开，避免按住时反复切换
+    fn set_pen_mode(&mut self, mode: PenMode, source: Option<KeySource>) -> Result<()> {
+        if mode == self.pen_mode {
+            return Ok(());
+        }
+        // 先在原来的模式下结束画笔的所有状态，笔尖之后会在新的模式下重新进入感应区域
+        match self.pen_mode {
+            PenMode::Absolute => {
+                if self.stylus.in_area {
+                    self.write_digitizer_leave_area()?;
+                }
+            }
+            PenMode::Relative => {
+                self.handle_relative_event(false, false, false, false, 0, 0)?;
+            }
+        }
+        match source {
+            Some(KeySource::StylusButton0) => self.stylus.button0_pressed = true,
+            Some(KeySource::StylusButton1) => self.stylus.button1_pressed = true,
+            _ => {}
+        }
+        self.pen_mode = mode;
+        match mode {
+            PenMode::Absolute => {
+                info!("已切换到绝对模式（绘图板模式）");
+            }
+            PenMode::Relative => {
+                info!("已切换到相对模式（鼠标模式）");
+            }
+        }
+        Ok(())
+    }
+
+    fn toggle_stabilizer(&mut self) {
+        self.set_stabilizer_enabled(!self.stabilizer_enabled);
+    }
+
+    fn set_stabilizer_enabled(&mut self, enabled: bool) {
+        self.stabilizer_enabled = enabled;
+        if enabled {
+            // 新的稳定器从真实笔尖的位置开始，笔画中途开启也不会跳动
+            if self.stabilizer.is_none() {
+                self.stabilizer = Some(Stabilizer::new(self.conf.stabilizer.length));
+                info!("已开启笔画稳定器：绳长({})", self.conf.stabilizer.length);
+            }
+        } else if self.stylus.tip_pressed {
+            // 笔画中途关闭会使笔尖从落后的位置跳到真实位置，因此等到抬笔后再关闭
+            info!("笔画稳定器将在笔画结束后关闭");
+        } else if self.stabilizer.take().is_some() {
+            info!("已关闭笔画稳定器");
+        }
+    }
+
+    fn enter_precision_mode(&mut self) {
+        if self.mapping.precision {
+            return;
+        }
+        // 以笔尖当前所在位置为中心，把整个绘图板映射到屏幕上的一小块区域
+        let scale = self.conf.precision_mode_scale;
+        let x_output_max = self.conf.x_max_value as i32 * self.conf.axis_scale;
+        let y_output_max = self.conf.y_max_value as i32 * self.conf.axis_scale;
+        let center_x = self.stylus.x as f32 / x_output_max as f32;
+        let center_y = 1f32 - self.stylus.y as f32 / y_output_max as f32; // Y坐标在上报前翻转过一次
+        self.mapping.x_map = Some(precision_map(center_x, scale));
+        self.mapping.y_map = Some(precision_map(center_y, scale));
+        self.mapping.precision = true;
+        info!(
+            "已进入精确模式：坐标({},{})，比例({})",
+            self.stylus.x, self.stylus.y, scale
+        );
+    }
+
+    fn exit_precision_mode(&mut self) {
+        if !self.mapping.precision {
+            return;
+        }
+        self.mapping.precision_held = None;
+        self.apply_area();
+        info!("已退出精确模式");
+    }
+
+    // 新的配置用到了虚拟键盘尚未启用的按键码；先释放所有按键，再用新的按键码重新创建虚拟键盘
+    fn recreate_keyboard(
+        &mut self,
+        keyboard_codes: BTreeSet<EV_KEY>,
+        consumer_codes: BTreeSet<EV_KEY>,
+    ) -> Result<()> {
+        self.flush_scheduled_keys(true)?;
+        let latched = self.latched_keys.take().map(|x| x.codes);
+        let pressed = self
+            .pressed_keys
+            .drain()
+            .flat_map(|(_, pressed)| pressed.codes)
+            .chain(latched.into_iter().flatten())
+            .chain(self.toggled_keys.drain(..).flat_map(|x| x.to_vec()))
+            .collect::<BTreeSet<_>>();
+        let mut devices = vec![];
+        for code in pressed {
+            devices.push(self.write_key_event(code, 0)?);
+        }
+        self.write_key_sync(&devices)?;
+        let evdev = open_evdev().context("open_evdev")?;
+        let keyboard =
+            create_uninit_keyboard_from_evdev(&evdev, VIRTUAL_KEYBOARD_NAME, &keyboard_codes)
+                .context("create_uninit_keyboard_from_evdev")?;
+        let consumer = create_uninit_keyboard_from_evdev(
+            &evdev,
+            VIRTUAL_CONSUMER_CONTROL_NAME,
+            &consumer_codes,
+        )
+        .context("create_uninit_keyboard_from_evdev")?;
+        // 两个虚拟设备都创建成功后才替换原先的设备
+        let keyboard_uinput = UInputDevice::create_from_device(&keyboard)
+            .context("UInputDevice::create_from_device")?;
+        let consumer_uinput = UInputDevice::create_from_device(&consumer)
+            .context("UInputDevice::create_from_device")?;
+        self.keyboard_uinput = keyboard_uinput;
+        self.consumer_uinput = consumer_uinput;
+        info!(
+            "已重新创建虚拟键盘：按键码数量({})，多媒体按键码数量({})",
+            keyboard_codes.len(),
+            consumer_codes.len()
+        );
+        self.keyboard_codes = keyboard_codes;
+        self.consumer_codes = consumer_codes;
+        Ok(())
+    }
+
+    fn replace_pad(&mut self, pad_uinput: Option<UInputDevice>) -> Result<()> {
+        if let Some(code) = self.pad.pressed {
+            self.release_pad_button(code)?;
+        }
+        match pad_uinput {
+            Some(_) => {
+                info!("已创建虚拟按键面板");
+            }
+            None => {
+                info!("已移除虚拟按键面板");
+            }
+        }
+        self.pad_uinput = pad_uinput;
+        Ok(())
+    }
+
+    // 多媒体与系统按键由单独的虚拟设备上报；BTN_*只可能来自doubleclick:，由虚拟鼠标上报。返回上报事件的设备
+    fn write_key_event(&self, code: EV_KEY, value: i32) -> Result<KeyDevice> {
+        if !binding::is_keyboard_key(code) {
+            self.write_pointer_event(EventCode::EV_KEY(code), value)?;
+            Ok(KeyDevice::Pointer)
+        } else if self.consumer_codes.contains(&code) {
+            self.write_consumer_event(EventCode::EV_KEY(code), value)?;
+            Ok(KeyDevice::Consumer)
+        } else {
+            self.write_keyboard_event(EventCode::EV_KEY(code), value)?;
+            Ok(KeyDevice::Keyboard)
+        }
+    }
+
+    // 只向这一帧中上报了事件的设备写入SYN_REPORT，避免其他设备产生空的帧
+    fn write_key_sync(&self, devices: &[KeyDevice]) -> Result<()> {
+        let code = EventCode::EV_SYN(EV_SYN::SYN_REPORT);
+        if devices.contains(&KeyDevice::Keyboard) {
+            self.write_keyboard_event(code, 0)?;
+        }
+        if devices.contains(&KeyDevice::Consumer) {
+            self.write_consumer_event(code, 0)?;
+        }
+        if devices.contains(&KeyDevice::Pointer) {
+            self.write_pointer_event(code, 0)?;
+        }
+        Ok(())
     }
 
     fn write_keyboard_event(&self, code: EventCode, value: i32) -> Result<()> {
-        let dummy_timeval = TimeVal::new(0, 0);
         self.keyboard_uinput
-            .write_event(&InputEvent::new(&dummy_timeval, &code, value))
+            .write_event(&InputEvent::new(&self.event_timeval(), &code, value))
             .context("UInputDevice::write_event(keyboard)")
     }
 
+    fn write_consumer_event(&self, code: EventCode, value: i32) -> Result<()> {
+        self.consumer_uinput
+            .write_event(&InputEvent::new(&self.event_timeval(), &code, value))
+            .context("UInputDevice::write_event(consumer)")
+    }
+
     fn handle_digitizer_event(&mut self, buf: &[u8]) -> Result<()> {
         let stylus_in_area = match buf[0] & 0xf0 {
             0xa0 => true,
@@ -303,35 +1244,77 @@ impl DriverTask {
             y_tilt
         );
 
+        // 防抖滤波；笔尖刚进入感应区域时重置滤波器，保证第一个坐标不会滞后
+        let (x, y) = match &mut self.filter {
+            Some(filter) => {
+                if stylus_in_area && !self.stylus.in_area {
+                    filter.reset();
+                }
+                filter.filter(x, y, self.event_time)
+            }
+            None => (x, y),
+        };
+
+        // 笔画稳定器仅在笔尖按下时生效；笔尖抬起前先把虚拟笔尖移动到真实笔尖的位置，保证笔画在抬笔处结束
+        let (x, y) = match &mut self.stabilizer {
+            Some(stabilizer) if stylus_touching => stabilizer.update(x, y),
+            Some(stabilizer) => {
+                let lagging = self.stylus.tip_pressed && stabilizer.lagging(x, y);
+                stabilizer.reset();
+                if lagging {
+                    debug!("虚拟绘图板 - 笔画稳定器追上真实笔尖");
+                    let mut report = self.write_digitizer_x(x, false)?;
+                    report |= self.write_digitizer_y(y, false)?;
+                    if report {
+                        self.write_digitizer_sync()?;
+                    }
+                }
+                (x, y)
+            }
+            None => (x, y),
+        };
+        if !stylus_touching && !self.stabilizer_enabled && self.stabilizer.take().is_some() {
+            info!("已关闭笔画稳定器");
+        }
+
+        // 按住滚动键且笔尖悬空时，画笔的移动转换为滚轮事件，指针保持不动
+        if self.scroll.held.is_some() {
+            if stylus_in_area && !stylus_touching {
+                return self.handle_scroll_event(stylus0_pressed, stylus1_pressed, x, y);
+            }
+            self.scroll.last_position = None;
+        }
+
+        if let PenMode::Relative = self.pen_mode {
+            return self.handle_relative_event(
+                stylus_in_area,
+                stylus_touching,
+                stylus0_pressed,
+                stylus1_pressed,
+                x,
+                y,
+            );
+        }
+
         // 进入/离开感应区域
         {
             if stylus_in_area {
                 if !self.stylus.in_area {
                     debug!("虚拟绘图板 - 笔尖进入感应区域");
                     self.stylus.in_area = true;
-                    self.write_digitizer_event(EventCode::EV_KEY(EV_KEY::BTN_TOOL_PEN), 1)?;
+                    self.write_digitizer_tool(1)?;
                     {
                         self.write_digitizer_x(x, true)?;
                         self.write_digitizer_y(y, true)?;
                     }
-                    self.write_digitizer_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+                    self.write_digitizer_sync()?;
                     return Ok(());
                 }
             } else {
                 if self.stylus.in_area {
-                    {
-                        self.write_digitizer_tip_released()?;
-                        self.write_digitizer_x(x, false)?;
-                        self.write_digitizer_y(y, false)?;
-                        self.write_digitizer_tilt_x(0)?;
-                        self.write_digitizer_tilt_y(0)?;
-                        self.write_digitizer_button0_released()?;
-                        self.write_digitizer_button1_released()?;
-                    }
-                    debug!("虚拟绘图板 - 笔尖离开感应区域");
-                    self.write_digitizer_event(EventCode::EV_KEY(EV_KEY::BTN_TOOL_PEN), 0)?;
-                    self.stylus.in_area = false;
-                    self.write_digitizer_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+                    self.write_digitizer_x(x, false)?;
+                    self.write_digitizer_y(y, false)?;
+                    self.write_digitizer_leave_area()?;
                     return Ok(());
                 }
             }
@@ -343,14 +1326,14 @@ impl DriverTask {
                 if self.write_digitizer_tip_pressed()? {
                     self.write_digitizer_x(x, false)?;
                     self.write_digitizer_y(y, false)?;
-                    self.write_digitizer_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+                    self.write_digitizer_sync()?;
                     return Ok(());
                 }
             } else {
                 if self.write_digitizer_tip_released()? {
                     self.write_digitizer_x(x, false)?;
                     self.write_digitizer_y(y, false)?;
-                    self.write_digitizer_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+                    self.write_digitizer_sync()?;
                     return Ok(());
                 }
             }
@@ -374,50 +1357,289 @@ impl DriverTask {
         report |= self.write_digitizer_tilt_x(x_tilt)?;
         report |= self.write_digitizer_tilt_y(y_tilt)?;
         if report {
-            self.write_digitizer_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+            self.write_digitizer_sync()?;
+        }
+        Ok(())
+    }
+
+    fn write_digitizer_leave_area(&mut self) -> Result<()> {
+        {
+            self.write_digitizer_tip_released()?;
+            self.write_digitizer_tilt_x(0)?;
+            self.write_digitizer_tilt_y(0)?;
+            self.write_digitizer_button0_released()?;
+            self.write_digitizer_button1_released()?;
+        }
+        debug!("虚拟绘图板 - 笔尖离开感应区域");
+        self.write_digitizer_tool(0)?;
+        self.stylus.in_area = false;
+        self.write_digitizer_sync()
+    }
+
+    fn handle_relative_event(
+        &mut self,
+        in_area: bool,
+        touching: bool,
+        stylus0_pressed: bool,
+        stylus1_pressed: bool,
+        x: u16,
+        y: u16,
+    ) -> Result<()> {
+        let mut report = false;
+        if in_area {
+            report |= self.write_pointer_motion(x, y)?;
+        } else if self.relative.last_position.take().is_some() {
+            debug!("虚拟鼠标 - 笔尖离开感应区域");
+        }
+        if in_area && touching {
+            if !self.relative.tip_pressed {
+                debug!("虚拟鼠标 - 按下左键");
+                self.relative.tip_pressed = true;
+                self.flush_pad_input()?;
+                self.latch_stroke(true);
+                self.write_pointer_event(EventCode::EV_KEY(EV_KEY::BTN_LEFT), 1)?;
+                report = true;
+            }
+        } else if self.relative.tip_pressed {
+            debug!("虚拟鼠标 - 释放左键");
+            self.relative.tip_pressed = false;
+            self.latch_stroke(false);
+            self.write_pointer_event(EventCode::EV_KEY(EV_KEY::BTN_LEFT), 0)?;
+            report = true;
+        }
+        if in_area && stylus0_pressed {
+            report |= self.write_digitizer_button0_pressed()?;
+        } else {
+            report |= self.write_digitizer_button0_released()?;
+        }
+        if in_area && stylus1_pressed {
+            report |= self.write_digitizer_button1_pressed()?;
+        } else {
+            report |= self.write_digitizer_button1_released()?;
+        }
+        if report {
+            self.write_pointer_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
         }
         Ok(())
     }
 
+    fn handle_scroll_event(
+        &mut self,
+        stylus0_pressed: bool,
+        stylus1_pressed: bool,
+        x: u16,
+        y: u16,
+    ) -> Result<()> {
+        // 笔侧键的释放仍需检测，以便结束滚动
+        let mut report = false;
+        if stylus0_pressed {
+            report |= self.write_digitizer_button0_pressed()?;
+        } else {
+            report |= self.write_digitizer_button0_released()?;
+        }
+        if stylus1_pressed {
+            report |= self.write_digitizer_button1_pressed()?;
+        } else {
+            report |= self.write_digitizer_button1_released()?;
+        }
+        if report {
+            match self.pen_mode {
+                PenMode::Absolute => self.write_digitizer_sync()?,
+                PenMode::Relative => {
+                    self.write_pointer_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?
+                }
+            }
+        }
+        if self.scroll.held.is_none() {
+            return Ok(());
+        }
+        if self.write_pointer_scroll(x, y)? {
+            self.write_pointer_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)?;
+        }
+        Ok(())
+    }
+
+    fn write_pointer_scroll(&mut self, x: u16, y: u16) -> Result<bool> {
+        let (last_x, last_y) = match self.scroll.last_position.replace((x, y)) {
+            Some(position) => position,
+            None => {
+                self.scroll.remainder = (0f32, 0f32);
+                self.scroll.detent = (0, 0);
+                return Ok(false);
+            }
+        };
+        // 默认为拖动画布的方向：画笔向下移动时向上滚动，向右移动时向左滚动
+        let conf = self.conf.scroll;
+        let mut dx = (last_x as f32 - x as f32) * conf.speed;
+        let mut dy = (last_y as f32 - y as f32) * conf.speed;
+        if conf.invert_x {
+            dx = -dx;
+        }
+        if conf.invert_y {
+            dy = -dy;
+        }
+        let dx = dx + self.scroll.remainder.0;
+        let dy = dy + self.scroll.remainder.1;
+        self.scroll.remainder = (dx.fract(), dy.fract());
+        let (dx, dy) = (dx.trunc() as i32, dy.trunc() as i32);
+        let report = self.write_pointer_wheel(dx, dy)?;
+        if report {
+            debug!("虚拟鼠标 - 上报滚动({},{})", dx, dy);
+        }
+        Ok(report)
+    }
+
+    // 上报高精度滚动量，同时为不支持高精度滚动的程序上报累计满一格的滚动
+    fn write_pointer_wheel(&mut self, dx: i32, dy: i32) -> Result<bool> {
+        let mut report = false;
+        if dx != 0 {
+            self.write_pointer_event(EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES), dx)?;
+            self.scroll.detent.0 += dx;
+            let detents = self.scroll.detent.0 / Wheel::HI_RES_PER_STEP;
+            if detents != 0 {
+                self.scroll.detent.0 %= Wheel::HI_RES_PER_STEP;
+                self.write_pointer_event(EventCode::EV_REL(EV_REL::REL_HWHEEL), detents)?;
+            }
+            report = true;
+        }
+        if dy != 0 {
+            self.write_pointer_event(EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES), dy)?;
+            self.scroll.detent.1 += dy;
+            let detents = self.scroll.detent.1 / Wheel::HI_RES_PER_STEP;
+            if detents != 0 {
+                self.scroll.detent.1 %= Wheel::HI_RES_PER_STEP;
+                self.write_pointer_event(EventCode::EV_REL(EV_REL::REL_WHEEL), detents)?;
+            }
+            report = true;
+        }
+        Ok(report)
+    }
+
+    fn write_pointer_motion(&mut self, x: u16, y: u16) -> Result<bool> {
+        let (last_x, last_y) = match self.relative.last_position.replace((x, y)) {
+            Some(position) => position,
+            None => {
+                // 笔尖刚进入感应区域，不产生位移
+                debug!("虚拟鼠标 - 笔尖进入感应区域");
+                self.relative.last_time = self.event_time;
+                self.relative.remainder = (0f32, 0f32);
+                return Ok(false);
+            }
+        };
+        let dx = x as f32 - last_x as f32;
+        let dy = last_y as f32 - y as f32; // Y坐标需要翻转
+        let dt = self.event_time.saturating_sub(self.relative.last_time);
+        self.relative.last_time = self.event_time;
+
+        // 按移动速度（设备坐标/毫秒）放大位移
+        let velocity = (dx * dx + dy * dy).sqrt() / (dt.as_secs_f32() * 1000f32).max(1f32);
+        let factor = self.conf.relative_mode.speed
+            * (1f32 + self.conf.relative_mode.acceleration * velocity);
+        let dx = dx * factor + self.relative.remainder.0;
+        let dy = dy * factor + self.relative.remainder.1;
+        self.relative.remainder = (dx.fract(), dy.fract());
+        let (dx, dy) = (dx.trunc() as i32, dy.trunc() as i32);
+
+        let mut report = false;
+        if dx != 0 {
+            self.write_pointer_event(EventCode::EV_REL(EV_REL::REL_X), dx)?;
+            report = true;
+        }
+        if dy != 0 {
+            self.write_pointer_event(EventCode::EV_REL(EV_REL::REL_Y), dy)?;
+            report = true;
+        }
+        if report {
+            debug!("虚拟鼠标 - 上报位移({},{})", dx, dy);
+        }
+        Ok(report)
+    }
+
+    fn write_pointer_event(&self, code: EventCode, value: i32) -> Result<()> {
+        self.pointer_uinput
+            .write_event(&InputEvent::new(&self.event_timeval(), &code, value))
+            .context("UInputDevice::write_event(pointer)")
+    }
+
+    // 笔侧键保持原有功能时，若画笔作为绘图板使用则上报为笔侧键，否则上报为配置的鼠标按键
+    fn write_stylus_button_event(&self, code: EV_KEY, value: i32) -> Result<()> {
+        let mouse_code = match code {
+            EV_KEY::BTN_STYLUS => self.conf.mouse_buttons.stylus_button0.code(),
+            _ => self.conf.mouse_buttons.stylus_button1.code(),
+        };
+        match (self.pen_mode, self.conf.digitizer_type) {
+            (PenMode::Absolute, DigitizerType::Pen) => {
+                self.write_digitizer_event(EventCode::EV_KEY(code), value)
+            }
+            (PenMode::Absolute, DigitizerType::Mouse) => {
+                self.write_digitizer_event(EventCode::EV_KEY(mouse_code), value)
+            }
+            (PenMode::Relative, _) => {
+                self.write_pointer_event(EventCode::EV_KEY(mouse_code), value)
+            }
+        }
+    }
+
+    // 绝对坐标的鼠标设备没有工具类型
+    fn write_digitizer_tool(&self, value: i32) -> Result<()> {
+        if let DigitizerType::Mouse = self.conf.digitizer_type {
+            return Ok(());
+        }
+        self.write_digitizer_event(EventCode::EV_KEY(EV_KEY::BTN_TOOL_PEN), value)
+    }
+
     fn write_digitizer_event(&self, code: EventCode, value: i32) -> Result<()> {
-        let dummy_timeval = TimeVal::new(0, 0);
         self.digitizer_uinput
-            .write_event(&InputEvent::new(&dummy_timeval, &code, value))
+            .write_event(&InputEvent::new(&self.event_timeval(), &code, value))
             .context("UInputDevice::write_event(digitizer)")
     }
 
+    // 结束虚拟绘图板上的一帧事件；附带的MSC_TIMESTAMP可让下游得知设备真实的报告频率与抖动
+    fn write_digitizer_sync(&self) -> Result<()> {
+        let timestamp = self.event_time.as_micros() as u32 as i32; // MSC_TIMESTAMP的单位为微秒，溢出后回绕
+        self.write_digitizer_event(EventCode::EV_MSC(EV_MSC::MSC_TIMESTAMP), timestamp)?;
+        self.write_digitizer_event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)
+    }
+
+    fn event_timeval(&self) -> TimeVal {
+        TimeVal::new(
+            self.event_time.as_secs() as i64,
+            self.event_time.subsec_micros() as i64,
+        )
+    }
+
     fn write_digitizer_x(&mut self, x: u16, force: bool) -> Result<bool> {
         let x = std::cmp::min(x, self.conf.x_max_value);
-        let x = match self.conf.x_map {
-            Some((min_ratio, max_ratio)) => (self.conf.x_max_value as f32 * min_ratio
-                + (x as f32 * (max_ratio - min_ratio)))
-                .round() as u16,
-            None => x,
-        };
+        let x = map_axis(
+            x,
+            self.conf.x_max_value,
+            self.conf.axis_scale,
+            self.mapping.x_map,
+        );
         if !force && x == self.stylus.x {
             return Ok(false);
         }
         debug!("虚拟绘图板 - 上报X坐标({})", x);
         self.stylus.x = x;
-        self.write_digitizer_event(EventCode::EV_ABS(EV_ABS::ABS_X), x as i32)?;
+        self.write_digitizer_event(EventCode::EV_ABS(EV_ABS::ABS_X), x)?;
         Ok(true)
     }
 
     fn write_digitizer_y(&mut self, y: u16, force: bool) -> Result<bool> {
         let y = std::cmp::min(y, self.conf.y_max_value);
-        let y = match self.conf.y_map {
-            Some((min_ratio, max_ratio)) => (self.conf.y_max_value as f32 * min_ratio
-                + (y as f32 * (max_ratio - min_ratio)))
-                .round() as u16,
-            None => y,
-        };
-        let y = self.conf.y_max_value - y; // 需要再翻转一次
+        let y = map_axis(
+            y,
+            self.conf.y_max_value,
+            self.conf.axis_scale,
+            self.mapping.y_map,
+        );
+        let y = self.conf.y_max_value as i32 * self.conf.axis_scale - y; // 需要再翻转一次
         if !force && y == self.stylus.y {
             return Ok(false);
         }
         debug!("虚拟绘图板 - 上报Y坐标({})", y);
         self.stylus.y = y;
-        self.write_digitizer_event(EventCode::EV_ABS(EV_ABS::ABS_Y), y as i32)?;
+        self.write_digitizer_event(EventCode::EV_ABS(EV_ABS::ABS_Y), y)?;
         Ok(true)
     }
 
@@ -427,6 +1649,12 @@ impl DriverTask {
         }
         debug!("虚拟绘图板 - 笔尖按下");
         self.stylus.tip_pressed = true;
+        self.flush_pad_input()?;
+        self.latch_stroke(true);
+        if let DigitizerType::Mouse = self.conf.digitizer_type {
+            self.write_digitizer_event(EventCode::EV_KEY(EV_KEY::BTN_LEFT), 1)?;
+            return Ok(true);
+        }
         self.write_digitizer_event(EventCode::EV_KEY(EV_KEY::BTN_TOUCH), 1)?;
         self.write_digitizer_tip_pressure(1, true)?;
         Ok(true)
@@ -438,12 +1666,20 @@ impl DriverTask {
         }
         debug!("虚拟绘图板 - 笔尖释放");
         self.stylus.tip_pressed = false;
+        self.latch_stroke(false);
+        if let DigitizerType::Mouse = self.conf.digitizer_type {
+            self.write_digitizer_event(EventCode::EV_KEY(EV_KEY::BTN_LEFT), 0)?;
+            return Ok(true);
+        }
         self.write_digitizer_event(EventCode::EV_KEY(EV_KEY::BTN_TOUCH), 0)?;
         self.write_digitizer_tip_pressure(0, true)?;
         Ok(true)
     }
 
     fn write_digitizer_tip_pressure(&mut self, pressure: u16, force: bool) -> Result<bool> {
+        if let DigitizerType::Mouse = self.conf.digitizer_type {
+            return Ok(false);
+        }
         if !force && pressure == self.stylus.pressure {
             return Ok(false);
         }
@@ -459,8 +1695,14 @@ impl DriverTask {
         }
         debug!("虚拟绘图板 - 按下下方的笔侧键");
         self.stylus.button0_pressed = true;
-        self.write_digitizer_event(EventCode::EV_KEY(EV_KEY::BTN_STYLUS), 1)?;
-        Ok(true)
+        let keymap = self.stylus_keymap(KeySource::StylusButton0);
+        if let Keymap::Passthrough = keymap {
+            self.stylus.button0_passthrough = true;
+            self.write_stylus_button_event(EV_KEY::BTN_STYLUS, 1)?;
+            return Ok(true);
+        }
+        self.press_keymap(keymap, KeySource::StylusButton0)?;
+        Ok(false)
     }
 
     fn write_digitizer_button0_released(&mut self) -> Result<bool> {
@@ -469,8 +1711,13 @@ impl DriverTask {
         }
         debug!("虚拟绘图板 - 释放下方的笔侧键");
         self.stylus.button0_pressed = false;
-        self.write_digitizer_event(EventCode::EV_KEY(EV_KEY::BTN_STYLUS), 0)?;
-        Ok(true)
+        if self.stylus.button0_passthrough {
+            self.stylus.button0_passthrough = false;
+            self.write_stylus_button_event(EV_KEY::BTN_STYLUS, 0)?;
+            return Ok(true);
+        }
+        self.release_keymap(KeySource::StylusButton0)?;
+        Ok(false)
     }
 
     fn write_digitizer_button1_pressed(&mut self) -> Result<bool> {
@@ -479,8 +1726,14 @@ impl DriverTask {
         }
         debug!("虚拟绘图板 - 按下上方的笔侧键");
         self.stylus.button1_pressed = true;
-        self.write_digitizer_event(EventCode::EV_KEY(EV_KEY::BTN_STYLUS2), 1)?;
-        Ok(true)
+        let keymap = self.stylus_keymap(KeySource::StylusButton1);
+        if let Keymap::Passthrough = keymap {
+            self.stylus.button1_passthrough = true;
+            self.write_stylus_button_event(EV_KEY::BTN_STYLUS2, 1)?;
+            return Ok(true);
+        }
+        self.press_keymap(keymap, KeySource::StylusButton1)?;
+        Ok(false)
     }
 
     fn write_digitizer_button1_released(&mut self) -> Result<bool> {
@@ -489,11 +1742,28 @@ impl DriverTask {
         }
         debug!("虚拟绘图板 - 释放上方的笔侧键");
         self.stylus.button1_pressed = false;
-        self.write_digitizer_event(EventCode::EV_KEY(EV_KEY::BTN_STYLUS2), 0)?;
-        Ok(true)
+        if self.stylus.button1_passthrough {
+            self.stylus.button1_passthrough = false;
+            self.write_stylus_button_event(EV_KEY::BTN_STYLUS2, 0)?;
+            return Ok(true);
+        }
+        self.release_keymap(KeySource::StylusButton1)?;
+        Ok(false)
+    }
+
+    // 未配置按键映射方案时，笔侧键保持原有功能
+    fn stylus_keymap(&self, source: KeySource) -> Keymap {
+        match (self.current_keymap(), source) {
+            (Some(keymap), KeySource::StylusButton0) => keymap.stylus_button0.clone(),
+            (Some(keymap), KeySource::StylusButton1) => keymap.stylus_button1.clone(),
+            _ => Keymap::Passthrough,
+        }
     }
 
     fn write_digitizer_tilt_x(&mut self, tilt_x: i8) -> Result<bool> {
+        if let DigitizerType::Mouse = self.conf.digitizer_type {
+            return Ok(false);
+        }
         if tilt_x == self.stylus.tilt_x {
             return Ok(false);
         }
@@ -504,6 +1774,9 @@ impl DriverTask {
     }
 
     fn write_digitizer_tilt_y(&mut self, tilt_y: i8) -> Result<bool> {
+        if let DigitizerType::Mouse = self.conf.digitizer_type {
+            return Ok(false);
+        }
         if tilt_y == self.stylus.tilt_y {
             return Ok(false);
         }
@@ -514,17 +1787,160 @@ impl DriverTask {
     }
 }
 
-fn create_uinput_device(conf: &mut Config) -> Result<(UInputDevice, UInputDevice)> {
+// 把设备坐标映射到虚拟绘图板的坐标范围[0, max_value * scale]内的指定比例区间
+//
+// 比例区间的端点先换算成虚拟绘图板的坐标，之后全程使用整数运算，避免缩小映射区域时经过浮点数舍入而丢失精度
+// 按键是否仍被按住：由某个来源按下、latch:锁定或toggle:按下
+fn is_key_held(
+    pressed_keys: &HashMap<KeySource, PressedKeys>,
+    latched_keys: &Option<LatchedKeys>,
+    toggled_keys: &[Arc<Vec<EV_KEY>>],
+    code: EV_KEY,
+) -> bool {
+    pressed_keys.values().any(|x| x.codes.contains(&code))
+        || latched_keys
+            .as_ref()
+            .is_some_and(|x| x.codes.contains(&code))
+        || toggled_keys.iter().any(|x| x.contains(&code))
+}
+
+fn map_axis(value: u16, max_value: u16, scale: i32, map: Option<(f32, f32)>) -> i32 {
+    let value = value as i64;
+    let max_value = max_value as i64;
+    let scale = scale as i64;
+    match map {
+        Some((min_ratio, max_ratio)) => {
+            let output_max = (max_value * scale) as f64;
+            let min = (output_max * min_ratio as f64).round() as i64;
+            let max = (output_max * max_ratio as f64).round() as i64;
+            if max_value == 0 {
+                return min as i32;
+            }
+            (min + (value * (max - min) + max_value / 2) / max_value) as i32
+        }
+        None => (value * scale) as i32,
+    }
+}
+
+// 计算以center为中心、长度为scale的比例区间，并保证区间不超出[0, 1]
+fn precision_map(center: f32, scale: f32) -> (f32, f32) {
+    let min = (center - scale / 2f32).clamp(0f32, 1f32 - scale);
+    (min, min + scale)
+}
+
+struct VirtualDevices {
+    digitizer: UInputDevice,
+    keyboard: UInputDevice,
+    consumer: UInputDevice,
+    pointer: UInputDevice,
+    pad: Option<UInputDevice>,
+}
+
+fn create_pad_uinput_device(evdev: &EventDevice) -> Result<UInputDevice> {
+    let pad = create_uninit_pad_from_evdev(evdev).context("create_uninit_pad_from_evdev")?;
+    UInputDevice::create_from_device(&pad).context("UInputDevice::create_from_device")
+}
+
+fn create_uinput_device(conf: &mut Config) -> Result<VirtualDevices> {
     let evdev = open_evdev().context("open_evdev")?;
     let digitizer = create_uninit_digitizer_from_evdev(conf, &evdev)
         .context("create_uninit_digitizer_from_evdev")?;
     let keyboard =
-        create_uninit_keyboard_from_evdev(&evdev).context("create_uninit_keyboard_from_evdev")?;
+        create_uninit_keyboard_from_evdev(&evdev, VIRTUAL_KEYBOARD_NAME, &conf.keyboard_codes())
+            .context("create_uninit_keyboard_from_evdev")?;
+    let consumer = create_uninit_keyboard_from_evdev(
+        &evdev,
+        VIRTUAL_CONSUMER_CONTROL_NAME,
+        &conf.consumer_codes(),
+    )
+    .context("create_uninit_keyboard_from_evdev")?;
+    let pointer =
+        create_uninit_pointer_from_evdev(&evdev).context("create_uninit_pointer_from_evdev")?;
     let digitizer_uinput =
         UInputDevice::create_from_device(&digitizer).context("UInputDevice::create_from_device")?;
     let keyboard_uinput =
         UInputDevice::create_from_device(&keyboard).context("UInputDevice::create_from_device")?;
-    Ok((digitizer_uinput, keyboard_uinput))
+    let consumer_uinput =
+        UInputDevice::create_from_device(&consumer).context("UInputDevice::create_from_device")?;
+    let pointer_uinput =
+        UInputDevice::create_from_device(&pointer).context("UInputDevice::create_from_device")?;
+    let pad_uinput = match conf.uses_pad() {
+        true => Some(create_pad_uinput_device(&evdev)?),
+        false => None,
+    };
+    Ok(VirtualDevices {
+        digitizer: digitizer_uinput,
+        keyboard: keyboard_uinput,
+        consumer: consumer_uinput,
+        pointer: pointer_uinput,
+        pad: pad_uinput,
+    })
+}
+
+// 虚拟绘图板与虚拟按键面板实际配置的设备信息
+pub struct VirtualTabletInfo {
+    pub bustype: u16,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub digitizer_name: String,
+    pub pad_name: String,
+    pub abs_x: AbsInfo,
+    pub abs_y: AbsInfo,
+    pub pressure: bool,
+    pub tilt: bool,
+    pub stylus_buttons: usize,
+    pub pad_buttons: Vec<EV_KEY>,
+    pub ring: bool,
+}
+
+// 按驱动运行时的方式构造虚拟设备（但不创建uinput设备），读取其配置
+pub fn virtual_tablet_info(conf: &mut Config) -> Result<VirtualTabletInfo> {
+    let evdev = open_evdev().context("open_evdev")?;
+    let digitizer = create_uninit_digitizer_from_evdev(conf, &evdev)
+        .context("create_uninit_digitizer_from_evdev")?;
+    let pad = create_uninit_pad_from_evdev(&evdev).context("create_uninit_pad_from_evdev")?;
+    let abs_info = |code| {
+        digitizer
+            .abs_info(&EventCode::EV_ABS(code))
+            .with_context(|| format!("虚拟绘图板没有{:?}", code))
+    };
+    let pad_buttons = [
+        EV_KEY::BTN_0,
+        EV_KEY::BTN_1,
+        EV_KEY::BTN_2,
+        EV_KEY::BTN_3,
+        EV_KEY::BTN_4,
+        EV_KEY::BTN_5,
+        EV_KEY::BTN_6,
+        EV_KEY::BTN_7,
+        EV_KEY::BTN_8,
+        EV_KEY::BTN_9,
+    ]
+    .into_iter()
+    .filter(|code| pad.has_event_code(&EventCode::EV_KEY(*code)))
+    .collect();
+    Ok(VirtualTabletInfo {
+        bustype: digitizer.bustype(),
+        vendor_id: digitizer.vendor_id(),
+        product_id: digitizer.product_id(),
+        digitizer_name: digitizer.name().unwrap_or_default().to_string(),
+        pad_name: pad.name().unwrap_or_default().to_string(),
+        abs_x: abs_info(EV_ABS::ABS_X)?,
+        abs_y: abs_info(EV_ABS::ABS_Y)?,
+        pressure: digitizer.has_event_code(&EventCode::EV_ABS(EV_ABS::ABS_PRESSURE)),
+        tilt: digitizer.has_event_code(&EventCode::EV_ABS(EV_ABS::ABS_TILT_X)),
+        stylus_buttons: [EV_KEY::BTN_STYLUS, EV_KEY::BTN_STYLUS2]
+            .iter()
+            .filter(|code| digitizer.has_event_code(&EventCode::EV_KEY(**code)))
+            .count(),
+        pad_buttons,
+        ring: pad.has_event_code(&EventCode::EV_ABS(EV_ABS::ABS_WHEEL)),
+    })
+}
+
+fn monotonic_time() -> Result<Duration> {
+    let ts = clock_gettime(ClockId::CLOCK_MONOTONIC).context("clock_gettime(CLOCK_MONOTONIC)")?;
+    Ok(Duration::new(ts.tv_sec() as u64, ts.tv_nsec() as u32))
 }
 
 fn open_evdev() -> Result<EventDevice> {
@@ -552,12 +1968,23 @@ macro_rules! enable_key_code {
     };
 }
 
+macro_rules! enable_rel_code {
+    ($ud:ident => $($code:ident),+ $(,)?) => {
+        $(
+            $ud.enable_event_code(&EventCode::EV_REL(EV_REL::$code), None).context(concat!("UninitDevice::enable_event_code(EV_REL::", stringify!($code), ")"))?;
+        )+
+    };
+}
+
 fn create_uninit_digitizer_from_evdev(
     conf: &mut Config,
     evdev: &EventDevice,
 ) -> Result<UninitDevice> {
     let ud = UninitDevice::new().context("UninitDevice::new")?;
-    ud.set_name(VIRTUAL_DIGITIZER_NAME);
+    match conf.digitizer_type {
+        DigitizerType::Pen => ud.set_name(VIRTUAL_DIGITIZER_NAME),
+        DigitizerType::Mouse => ud.set_name(VIRTUAL_ABSOLUTE_POINTER_NAME),
+    }
     ud.set_bustype(evdev.bustype());
     ud.set_vendor_id(evdev.vendor_id());
     ud.set_product_id(evdev.product_id());
@@ -584,6 +2011,14 @@ fn create_uninit_digitizer_from_evdev(
     } else {
         conf.x_max_value = abs_y.maximum as u16;
     }
+    if conf.axis_scale <= 0 {
+        conf.axis_scale = 1;
+    }
+    // 按比例放大虚拟绘图板的坐标范围与分辨率
+    abs_x.maximum *= conf.axis_scale;
+    abs_x.resolution *= conf.axis_scale;
+    abs_y.maximum *= conf.axis_scale;
+    abs_y.resolution *= conf.axis_scale;
     let abs_pressure = read_abs_info!(ABS_PRESSURE);
     let abs_tilt_x = read_abs_info!(ABS_TILT_X);
     let abs_tilt_y = read_abs_info!(ABS_TILT_Y);
@@ -610,21 +2045,34 @@ fn create_uninit_digitizer_from_evdev(
     }
     copy_abs_info!(ABS_X, &abs_y); // ABS_X与ABS_Y需要互相调换
     copy_abs_info!(ABS_Y, &abs_x); // ABS_X与ABS_Y需要互相调换
-    copy_abs_info!(ABS_PRESSURE, &abs_pressure);
-    copy_abs_info!(ABS_TILT_X, &abs_tilt_x);
-    copy_abs_info!(ABS_TILT_Y, &abs_tilt_y);
 
     ud.enable_event_type(&EventType::EV_SYN)
         .context("UninitDevice::enable_event_type(EV_SYN)")?;
+    ud.enable_event_code(&EventCode::EV_MSC(EV_MSC::MSC_TIMESTAMP), None)
+        .context("UninitDevice::enable_event_code(EV_MSC::MSC_TIMESTAMP)")?;
     ud.enable_property(&InputProp::INPUT_PROP_POINTER)
         .context("UninitDevice::enable_property(INPUT_PROP_POINTER)")?;
-    enable_key_code! { ud => BTN_TOOL_PEN, BTN_TOOL_RUBBER, BTN_TOUCH, BTN_STYLUS, BTN_STYLUS2 };
+    match conf.digitizer_type {
+        DigitizerType::Pen => {
+            copy_abs_info!(ABS_PRESSURE, &abs_pressure);
+            copy_abs_info!(ABS_TILT_X, &abs_tilt_x);
+            copy_abs_info!(ABS_TILT_Y, &abs_tilt_y);
+            enable_key_code! { ud => BTN_TOOL_PEN, BTN_TOOL_RUBBER, BTN_TOUCH, BTN_STYLUS, BTN_STYLUS2 };
+        }
+        DigitizerType::Mouse => {
+            enable_key_code! { ud => BTN_LEFT, BTN_RIGHT, BTN_MIDDLE, BTN_SIDE, BTN_EXTRA };
+        }
+    }
     Ok(ud)
 }
 
-fn create_uninit_keyboard_from_evdev(evdev: &EventDevice) -> Result<UninitDevice> {
+fn create_uninit_keyboard_from_evdev(
+    evdev: &EventDevice,
+    name: &str,
+    codes: &BTreeSet<EV_KEY>,
+) -> Result<UninitDevice> {
     let ud = UninitDevice::new().context("UninitDevice::new")?;
-    ud.set_name(VIRTUAL_KEYBOARD_NAME);
+    ud.set_name(name);
     ud.set_bustype(evdev.bustype());
     ud.set_vendor_id(evdev.vendor_id());
     ud.set_product_id(evdev.product_id());
@@ -634,20 +2082,66 @@ fn create_uninit_keyboard_from_evdev(evdev: &EventDevice) -> Result<UninitDevice
         .context("UninitDevice::enable_event_type(EV_SYN)")?;
     ud.enable_event_type(&EventType::EV_REP)
         .context("UninitDevice::enable_event_type(EV_REP)")?;
-    enable_key_code! { ud =>
-        KEY_A, KEY_B, KEY_C, KEY_D, KEY_E, KEY_F, KEY_G, KEY_H, KEY_I, KEY_J, KEY_K, KEY_L, KEY_M,
-        KEY_N, KEY_O, KEY_P, KEY_Q, KEY_R, KEY_S, KEY_T, KEY_U, KEY_V, KEY_W, KEY_X, KEY_Y, KEY_Z,
+    for code in codes {
+        ud.enable_event_code(&EventCode::EV_KEY(*code), None)
+            .with_context(|| format!("UninitDevice::enable_event_code(EV_KEY::{:?})", code))?;
+    }
+    Ok(ud)
+}
 
-        KEY_0, KEY_1, KEY_2, KEY_3, KEY_4, KEY_5, KEY_6, KEY_7, KEY_8, KEY_9,
+fn create_uninit_pointer_from_evdev(evdev: &EventDevice) -> Result<UninitDevice> {
+    let ud = UninitDevice::new().context("UninitDevice::new")?;
+    ud.set_name(VIRTUAL_POINTER_NAME);
+    ud.set_bustype(evdev.bustype());
+    ud.set_vendor_id(evdev.vendor_id());
+    ud.set_product_id(evdev.product_id());
+    ud.set_version(evdev.version());
+
+    ud.enable_event_type(&EventType::EV_SYN)
+        .context("UninitDevice::enable_event_type(EV_SYN)")?;
+    ud.enable_property(&InputProp::INPUT_PROP_POINTER)
+        .context("UninitDevice::enable_property(INPUT_PROP_POINTER)")?;
+    enable_rel_code! { ud => REL_X, REL_Y, REL_WHEEL, REL_HWHEEL, REL_WHEEL_HI_RES, REL_HWHEEL_HI_RES };
+    enable_key_code! { ud => BTN_LEFT, BTN_RIGHT, BTN_MIDDLE, BTN_SIDE, BTN_EXTRA };
+    Ok(ud)
+}
 
-        KEY_MINUS, KEY_EQUAL, KEY_BACKSLASH, KEY_GRAVE, KEY_LEFTBRACE, KEY_RIGHTBRACE,
-        KEY_SEMICOLON, KEY_APOSTROPHE, KEY_COMMA, KEY_DOT, KEY_SLASH,
+// udev通过BTN_STYLUS、BTN_0与ABS_X/ABS_Y识别绘图板按键面板，这几个事件码仅占位，不会上报
+fn create_uninit_pad_from_evdev(evdev: &EventDevice) -> Result<UninitDevice> {
+    let ud = UninitDevice::new().context("UninitDevice::new")?;
+    ud.set_name(VIRTUAL_PAD_NAME);
+    ud.set_bustype(evdev.bustype());
+    ud.set_vendor_id(evdev.vendor_id());
+    ud.set_product_id(evdev.product_id());
+    ud.set_version(evdev.version());
 
-        KEY_ESC, KEY_TAB, KEY_BACKSPACE, KEY_SPACE, KEY_ENTER,
-        KEY_HOME, KEY_END, KEY_PAGEUP, KEY_PAGEDOWN, KEY_INSERT, KEY_DELETE,
+    macro_rules! enable_abs_code {
+        ($code:ident, $maximum:expr) => {
+            ud.enable_event_code(
+                &EventCode::EV_ABS(EV_ABS::$code),
+                Some(evdev_rs::EnableCodeData::AbsInfo(AbsInfo {
+                    minimum: 0,
+                    maximum: $maximum,
+                    resolution: 0,
+                    value: 0,
+                    fuzz: 0,
+                    flat: 0,
+                })),
+            )
+            .context(concat!(
+                "UninitDevice::enable_event_code(",
+                stringify!($code),
+                ")"
+            ))?;
+        };
+    }
+    enable_abs_code!(ABS_X, 1);
+    enable_abs_code!(ABS_Y, 1);
+    enable_abs_code!(ABS_WHEEL, PAD_RING_MAX_VALUE);
 
-        KEY_LEFTCTRL, KEY_LEFTSHIFT, KEY_LEFTALT, KEY_LEFTMETA,
-    };
+    ud.enable_event_type(&EventType::EV_SYN)
+        .context("UninitDevice::enable_event_type(EV_SYN)")?;
+    enable_key_code! { ud => BTN_0, BTN_1, BTN_2, BTN_3, BTN_4, BTN_5, BTN_6, BTN_7, BTN_8, BTN_STYLUS };
     Ok(ud)
 }
 
@@ -693,3 +2187,60 @@ fn open_usb_device_handle() -> Result<UsbDeviceHandle<rusb::GlobalContext>> {
     }
     Ok(handle)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 遍历设备的所有坐标，返回映射后的虚拟坐标
+    fn sweep(max_value: u16, scale: i32, map: Option<(f32, f32)>) -> Vec<i32> {
+        (0..=max_value)
+            .map(|value| map_axis(value, max_value, scale, map))
+            .collect()
+    }
+
+    #[test]
+    fn map_axis_keeps_every_step_distinct() {
+        for max_value in [4095, 32767] {
+            for (scale, map) in [
+                (1, None),
+                (1, Some((0f32, 1f32))),
+                (4, None),
+                (4, Some((0.25, 0.5))),
+                (8, Some((0.4, 0.6))),
+                (64, Some((0.1, 0.2))),
+            ] {
+                let output = sweep(max_value, scale, map);
+                assert!(
+                    output.windows(2).all(|x| x[0] < x[1]),
+                    "max_value({}) scale({}) map({:?})",
+                    max_value,
+                    scale,
+                    map
+                );
+                let output_max = max_value as f64 * scale as f64;
+                let (min, max) = map.unwrap_or((0f32, 1f32));
+                assert_eq!(output[0], (output_max * min as f64).round() as i32);
+                assert_eq!(
+                    output[max_value as usize],
+                    (output_max * max as f64).round() as i32
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn map_axis_is_monotonic_when_narrowed() {
+        // 映射区域小于设备坐标范围且没有放大时，相邻的坐标可能相同，但不会倒退
+        let output = sweep(4095, 1, Some((0.25, 0.5)));
+        assert!(output.windows(2).all(|x| x[0] <= x[1]));
+        assert_eq!(output[0], 1024);
+        assert_eq!(output[4095], 2048);
+    }
+
+    #[test]
+    fn map_axis_with_zero_range() {
+        assert_eq!(map_axis(0, 0, 4, Some((0.5, 1f32))), 0);
+        assert_eq!(map_axis(0, 0, 4, None), 0);
+    }
+}
@@ -0,0 +1,85 @@
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow};
+use nix::sys::signal::{Signal, killpg};
+use nix::unistd::Pid;
+
+use crate::config::ExecConfig;
+use crate::{info, warn};
+
+const WAIT_INTERVAL: Duration = Duration::from_millis(100);
+
+// 以配置的普通用户身份在后台运行命令，不等待其结束；命令的输出写入日志，超时后结束其整个进程组
+pub fn spawn_command(conf: &ExecConfig, argv: &[String]) -> Result<()> {
+    if conf.uid == 0 || conf.gid == 0 {
+        return Err(anyhow!("不能以root身份运行命令"));
+    }
+    let mut child = Command::new(&argv[0])
+        .args(&argv[1..])
+        .env_clear()
+        .envs(&conf.env)
+        .current_dir("/")
+        .uid(conf.uid)
+        .gid(conf.gid)
+        .process_group(0)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("无法运行命令{:?}", argv))?;
+    let pid = child.id();
+    info!("运行命令{:?}，进程号{}", argv, pid);
+    if let Some(stdout) = child.stdout.take() {
+        log_output(pid, stdout, false);
+    }
+    if let Some(stderr) = child.stderr.take() {
+        log_output(pid, stderr, true);
+    }
+    let timeout = conf.timeout;
+    spawn(move || {
+        if let Err(e) = wait_command(child, timeout) {
+            warn!("等待命令（进程号{}）结束时发生错误: {:?}", pid, e);
+        }
+    });
+    Ok(())
+}
+
+// 逐行把命令的输出写入日志，标准错误输出记为警告
+fn log_output<R: Read + Send + 'static>(pid: u32, output: R, stderr: bool) {
+    spawn(move || {
+        for line in BufReader::new(output).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if stderr {
+                warn!("命令（进程号{}）: {}", pid, line);
+            } else {
+                info!("命令（进程号{}）: {}", pid, line);
+            }
+        }
+    });
+}
+
+// timeout为None时不限制运行时间
+fn wait_command(mut child: Child, timeout: Option<Duration>) -> Result<()> {
+    let pid = child.id();
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait().context("Child::try_wait")? {
+            info!("命令（进程号{}）已结束: {}", pid, status);
+            return Ok(());
+        }
+        if timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+            warn!("命令（进程号{}）运行超时，结束其进程组", pid);
+            killpg(Pid::from_raw(pid as i32), Signal::SIGKILL).context("killpg")?;
+            let status = child.wait().context("Child::wait")?;
+            info!("命令（进程号{}）已结束: {}", pid, status);
+            return Ok(());
+        }
+        sleep(WAIT_INTERVAL);
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::time::Duration;
+
+use crate::config::FilterConfig;
+
+// 笔尖坐标的防抖滤波器，X轴与Y轴分别独立滤波
+pub struct PositionFilter {
+    x: AxisFilter,
+    y: AxisFilter,
+    last_timestamp: Option<Duration>,
+}
+impl PositionFilter {
+    pub fn new(conf: FilterConfig) -> Self {
+        Self {
+            x: AxisFilter::new(conf),
+            y: AxisFilter::new(conf),
+            last_timestamp: None,
+        }
+    }
+
+    // 清空滤波器的历史状态，下一个坐标将原样输出
+    pub fn reset(&mut self) {
+        self.x.reset();
+        self.y.reset();
+        self.last_timestamp = None;
+    }
+
+    pub fn filter(&mut self, x: u16, y: u16, timestamp: Duration) -> (u16, u16) {
+        let dt = match self.last_timestamp {
+            Some(last) => timestamp.saturating_sub(last).as_secs_f32(),
+            None => 0f32,
+        };
+        self.last_timestamp = Some(timestamp);
+        let x = self.x.filter(x as f32, dt);
+        let y = self.y.filter(y as f32, dt);
+        (x.round() as u16, y.round() as u16)
+    }
+}
+
+enum AxisFilter {
+    MovingAverage {
+        window: usize,
+        values: VecDeque<f32>,
+    },
+    Exponential {
+        alpha: f32,
+        value: Option<f32>,
+    },
+    OneEuro {
+        min_cutoff: f32,
+        beta: f32,
+        d_cutoff: f32,
+        value: Option<f32>,
+        derivative: f32,
+    },
+}
+impl AxisFilter {
+    fn new(conf: FilterConfig) -> Self {
+        match conf {
+            FilterConfig::MovingAverage { window } => Self::MovingAverage {
+                window,
+                values: VecDeque::with_capacity(window),
+            },
+            FilterConfig::Exponential { alpha } => Self::Exponential { alpha, value: None },
+            FilterConfig::OneEuro {
+                min_cutoff,
+                beta,
+                d_cutoff,
+            } => Self::OneEuro {
+                min_cutoff,
+                beta,
+                d_cutoff,
+                value: None,
+                derivative: 0f32,
+            },
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            Self::MovingAverage { values, .. } => values.clear(),
+            Self::Exponential { value, .. } => *value = None,
+            Self::OneEuro {
+                value, derivative, ..
+            } => {
+                *value = None;
+                *derivative = 0f32;
+            }
+        }
+    }
+
+    fn filter(&mut self, x: f32, dt: f32) -> f32 {
+        match self {
+            Self::MovingAverage { window, values } => {
+                if values.len() >= *window {
+                    values.pop_front();
+                }
+                values.push_back(x);
+                values.iter().sum::<f32>() / values.len() as f32
+            }
+            Self::Exponential { alpha, value } => {
+                let filtered = match value {
+                    Some(prev) => *alpha * x + (1f32 - *alpha) * *prev,
+                    None => x,
+                };
+                value.replace(filtered);
+                filtered
+            }
+            Self::OneEuro {
+                min_cutoff,
+                beta,
+                d_cutoff,
+                value,
+                derivative,
+            } => {
+                // 见 https://gery.casiez.net/1euro/ ：低速时截止频率低以抑制抖动，高速时截止频率高以减少延迟
+                let prev = match value {
+                    Some(prev) => *prev,
+                    None => {
+                        value.replace(x);
+                        return x;
+                    }
+                };
+                if dt <= 0f32 {
+                    return prev;
+                }
+                let d = (x - prev) / dt;
+                *derivative += smoothing_factor(*d_cutoff, dt) * (d - *derivative);
+                let cutoff = *min_cutoff + *beta * derivative.abs();
+                let filtered = prev + smoothing_factor(cutoff, dt) * (x - prev);
+                value.replace(filtered);
+                filtered
+            }
+        }
+    }
+}
+
+fn smoothing_factor(cutoff: f32, dt: f32) -> f32 {
+    let tau = 1f32 / (2f32 * PI * cutoff);
+    1f32 / (1f32 + tau / dt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn moving_average() {
+        let mut filter = PositionFilter::new(FilterConfig::MovingAverage { window: 2 });
+        assert_eq!(filter.filter(100, 100, STEP), (100, 100));
+        assert_eq!(filter.filter(110, 90, STEP * 2), (105, 95));
+        assert_eq!(filter.filter(130, 90, STEP * 3), (120, 90));
+    }
+
+    #[test]
+    fn exponential() {
+        let mut filter = PositionFilter::new(FilterConfig::Exponential { alpha: 0.5 });
+        assert_eq!(filter.filter(100, 100, STEP), (100, 100));
+        assert_eq!(filter.filter(120, 100, STEP * 2), (110, 100));
+        filter.reset();
+        assert_eq!(filter.filter(300, 300, STEP * 3), (300, 300));
+    }
+
+    #[test]
+    fn one_euro() {
+        let conf = FilterConfig::OneEuro {
+            min_cutoff: 1f32,
+            beta: 0f32,
+            d_cutoff: 1f32,
+        };
+        let mut filter = PositionFilter::new(conf);
+        assert_eq!(filter.filter(100, 100, STEP), (100, 100));
+        // 时间没有前进时保持上一个坐标
+        assert_eq!(filter.filter(200, 200, STEP), (100, 100));
+        let (x, y) = filter.filter(200, 100, STEP * 2);
+        assert!(x > 100 && x < 200);
+        assert_eq!(y, 100);
+    }
+}
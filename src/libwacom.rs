@@ -0,0 +1,225 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use evdev_rs::enums::EV_KEY;
+
+use crate::config::Config;
+use crate::driver::{VirtualTabletInfo, virtual_tablet_info};
+use crate::info;
+
+const TABLET_NAME: &str = "Parblo Intangbo S";
+const FILE_STEM: &str = "parblo-intangbo-s";
+const STYLUS_ID: u32 = 0x1;
+const MM_PER_INCH: f32 = 25.4;
+
+// 生成描述虚拟设备的libwacom数据文件（.tablet与.stylus），写入到指定目录
+pub fn generate<P: AsRef<Path>>(mut conf: Config, dir: P) -> Result<()> {
+    let dir = dir.as_ref();
+    let info = virtual_tablet_info(&mut conf).context("无法读取虚拟设备的配置")?;
+    std::fs::create_dir_all(dir).with_context(|| format!("无法创建目录{}", dir.display()))?;
+    for (ext, content) in [
+        ("tablet", tablet_file(&info)),
+        ("stylus", stylus_file(&info)),
+    ] {
+        let path = dir.join(format!("{}.{}", FILE_STEM, ext));
+        std::fs::write(&path, content).with_context(|| format!("无法写入{}", path.display()))?;
+        info!("已生成{}", path.display());
+    }
+    Ok(())
+}
+
+fn tablet_file(info: &VirtualTabletInfo) -> String {
+    let bus = match info.bustype {
+        0x03 => "usb",
+        0x05 => "bluetooth",
+        _ => "serial",
+    };
+    let matches = [&info.digitizer_name, &info.pad_name]
+        .iter()
+        .map(|name| {
+            format!(
+                "{}|{:04x}|{:04x}|{}",
+                bus, info.vendor_id, info.product_id, name
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let mut s = String::new();
+    let _ = writeln!(s, "[Device]");
+    let _ = writeln!(s, "Name={}", TABLET_NAME);
+    let _ = writeln!(s, "DeviceMatch={};", matches);
+    let _ = writeln!(s, "Class=Bamboo");
+    // 尺寸以整数英寸为单位（libwacom按整数读取），由坐标范围与分辨率（单位/毫米）计算得出；不足0.5英寸时省略
+    if let (Some(width), Some(height)) = (size_inch(&info.abs_x), size_inch(&info.abs_y)) {
+        let _ = writeln!(s, "Width={}", width);
+        let _ = writeln!(s, "Height={}", height);
+    }
+    let _ = writeln!(s, "Styli={:#x}:{:#x};", info.vendor_id, STYLUS_ID);
+    let _ = writeln!(s);
+    let _ = writeln!(s, "[Features]");
+    let _ = writeln!(s, "Stylus=true");
+    let _ = writeln!(s, "Reversible=false");
+    let _ = writeln!(s, "Touch=false");
+    let _ = writeln!(s, "Ring={}", info.ring);
+    let _ = writeln!(s, "NumStrips=0");
+    let _ = writeln!(s);
+
+    // 按钮依次以字母命名；绘图板的按钮与转环均位于左侧，BTN_8为转环中心按钮
+    let letters = ('A'..='Z').take(info.pad_buttons.len()).collect::<Vec<_>>();
+    let join = |letters: &[char]| {
+        letters
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(";")
+    };
+    let ring_buttons = info
+        .pad_buttons
+        .iter()
+        .zip(&letters)
+        .filter(|(code, _)| **code == EV_KEY::BTN_8)
+        .map(|(_, c)| *c)
+        .collect::<Vec<_>>();
+    let _ = writeln!(s, "[Buttons]");
+    let _ = writeln!(s, "Left={};", join(&letters));
+    if info.ring {
+        let _ = writeln!(s, "Ring={};", join(&ring_buttons));
+    }
+    let _ = writeln!(
+        s,
+        "EvdevCodes={};",
+        info.pad_buttons
+            .iter()
+            .map(|code| format!("{:?}", code))
+            .collect::<Vec<_>>()
+            .join(";")
+    );
+    s
+}
+
+fn stylus_file(info: &VirtualTabletInfo) -> String {
+    let mut axes = vec![];
+    if info.tilt {
+        axes.push("Tilt");
+    }
+    if info.pressure {
+        axes.push("Pressure");
+    }
+
+    let mut s = String::new();
+    let _ = writeln!(s, "[{:#x}:{:#x}]", info.vendor_id, STYLUS_ID);
+    let _ = writeln!(s, "Name={} Pen", TABLET_NAME);
+    let _ = writeln!(s, "Group={}", FILE_STEM);
+    let _ = writeln!(s, "Buttons={}", info.stylus_buttons);
+    let _ = writeln!(s, "Axes={};", axes.join(";"));
+    let _ = writeln!(s, "Type=General");
+    s
+}
+
+fn size_inch(abs: &evdev_rs::AbsInfo) -> Option<u32> {
+    if abs.resolution <= 0 {
+        return None;
+    }
+    let mm = (abs.maximum - abs.minimum) as f32 / abs.resolution as f32;
+    let inch = (mm / MM_PER_INCH).round() as u32;
+    (inch > 0).then_some(inch)
+}
+
+#[cfg(test)]
+mod tests {
+    use evdev_rs::AbsInfo;
+
+    use super::*;
+
+    fn abs(maximum: i32, resolution: i32) -> AbsInfo {
+        AbsInfo {
+            value: 0,
+            minimum: 0,
+            maximum,
+            fuzz: 0,
+            flat: 0,
+            resolution,
+        }
+    }
+
+    fn tablet_info() -> VirtualTabletInfo {
+        VirtualTabletInfo {
+            bustype: 0x03,
+            vendor_id: 0x0483,
+            product_id: 0xa014,
+            digitizer_name: "Parblo Intangbo S (Digitizer)".to_string(),
+            pad_name: "Parblo Intangbo S Pad".to_string(),
+            // 216.7mm x 135.4mm
+            abs_x: abs(21670, 100),
+            abs_y: abs(13540, 100),
+            pressure: true,
+            tilt: false,
+            stylus_buttons: 2,
+            pad_buttons: vec![EV_KEY::BTN_0, EV_KEY::BTN_1, EV_KEY::BTN_8],
+            ring: true,
+        }
+    }
+
+    #[test]
+    fn tablet_file_content() {
+        assert_eq!(
+            tablet_file(&tablet_info()),
+            "[Device]
+Name=Parblo Intangbo S
+DeviceMatch=usb|0483|a014|Parblo Intangbo S (Digitizer);usb|0483|a014|Parblo Intangbo S Pad;
+Class=Bamboo
+Width=9
+Height=5
+Styli=0x483:0x1;
+
+[Features]
+Stylus=true
+Reversible=false
+Touch=false
+Ring=true
+NumStrips=0
+
+[Buttons]
+Left=A;B;C;
+Ring=C;
+EvdevCodes=BTN_0;BTN_1;BTN_8;
+"
+        );
+    }
+
+    #[test]
+    fn tablet_file_without_size_or_ring() {
+        let mut info = tablet_info();
+        info.abs_x = abs(1000, 100);
+        info.ring = false;
+        info.pad_buttons = vec![EV_KEY::BTN_0];
+        let content = tablet_file(&info);
+        assert!(!content.contains("Width="));
+        assert!(!content.contains("Height="));
+        assert!(content.contains("Ring=false\n"));
+        assert!(content.ends_with("[Buttons]\nLeft=A;\nEvdevCodes=BTN_0;\n"));
+    }
+
+    #[test]
+    fn stylus_file_content() {
+        assert_eq!(
+            stylus_file(&tablet_info()),
+            "[0x483:0x1]
+Name=Parblo Intangbo S Pen
+Group=parblo-intangbo-s
+Buttons=2
+Axes=Pressure;
+Type=General
+"
+        );
+    }
+
+    #[test]
+    fn size_is_rounded_to_whole_inches() {
+        assert_eq!(size_inch(&abs(21670, 100)), Some(9));
+        assert_eq!(size_inch(&abs(1000, 100)), None);
+        assert_eq!(size_inch(&abs(21670, 0)), None);
+    }
+}
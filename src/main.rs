@@ -1,3 +1,4 @@
+use std::path::Path;
 use std::thread::spawn;
 
 use anyhow::{Context, Result};
@@ -9,13 +10,33 @@ use crate::{
     signal::ExitSignal,
 };
 
+mod binding;
 mod cancel;
 mod config;
+mod display;
 mod driver;
+mod exec;
+mod filter;
+mod libwacom;
 mod macros;
+mod schedule;
 mod signal;
+mod stabilizer;
+mod typing;
 
 fn main() -> Result<()> {
+    // 子命令：libwacom <输出目录> [配置文件]
+    if std::env::args().nth(1).as_deref() == Some("libwacom") {
+        let dir = std::env::args()
+            .nth(2)
+            .context("缺少libwacom数据文件的输出目录")?;
+        let conf = match std::env::args().nth(3) {
+            Some(path) => Config::load(path).context("加载配置文件失败")?,
+            None => Config::default(),
+        };
+        return libwacom::generate(conf, dir).context("生成libwacom数据文件失败");
+    }
+
     let ct = CancelToken::new();
 
     let conf_path = std::env::args().nth(1);
@@ -24,6 +45,8 @@ fn main() -> Result<()> {
         None => Config::default(),
     };
 
+    log_outputs(&conf);
+
     let exit_signal = ExitSignal::new(ct.clone())?;
 
     let mut watch_config_change_task = None;
@@ -61,3 +84,33 @@ fn main() -> Result<()> {
     }
     Ok(())
 }
+
+fn log_outputs(conf: &Config) {
+    let root = conf
+        .drm_sysfs_root
+        .as_deref()
+        .unwrap_or(Path::new(display::DEFAULT_DRM_SYSFS_ROOT));
+    let outputs = match display::scan_outputs(root) {
+        Ok(outputs) => outputs,
+        Err(e) => {
+            warn!("无法扫描显示输出: {:?}", e);
+            return;
+        }
+    };
+    if outputs.is_empty() {
+        info!("没有检测到已连接的显示输出");
+    }
+    for output in outputs {
+        info!(
+            "检测到显示输出{}：{}x{}，显示器名称({})，物理尺寸({})",
+            output.connector,
+            output.width,
+            output.height,
+            output.name.as_deref().unwrap_or("未知"),
+            match output.size_mm {
+                Some((width, height)) => format!("{}x{}mm", width, height),
+                None => "未知".to_string(),
+            }
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use evdev_rs::enums::EV_KEY;
+
+use crate::binding;
+use crate::config::MacroStep;
+
+// 等待上报的按键事件队列，按时间先后排列；同一帧的事件一起上报，帧之间以SYN_REPORT分隔
+#[derive(Default)]
+pub struct KeyQueue {
+    keys: VecDeque<ScheduledKey>,
+    next_frame: u64,
+}
+struct ScheduledKey {
+    time: Duration,
+    frame: u64,
+    code: EV_KEY,
+    value: i32,
+}
+impl KeyQueue {
+    // 把若干帧按键事件加入队列，帧之间间隔delay；返回最后一帧的上报时间
+    pub fn schedule<const N: usize>(
+        &mut self,
+        start: Duration,
+        frames: [Vec<(EV_KEY, i32)>; N],
+        delay: Duration,
+    ) -> Duration {
+        // 排在已有的事件之后，保证事件的先后顺序
+        let mut time = match self.keys.back() {
+            Some(last) => start.max(last.time),
+            None => start,
+        };
+        let mut first = true;
+        for frame in frames {
+            if frame.is_empty() {
+                continue;
+            }
+            if !first {
+                time += delay;
+            }
+            first = false;
+            self.next_frame += 1;
+            for (code, value) in frame {
+                self.keys.push_back(ScheduledKey {
+                    time,
+                    frame: self.next_frame,
+                    code,
+                    value,
+                });
+            }
+        }
+        time
+    }
+
+    // 把宏的各个步骤加入队列，宏结束时释放仍然按下的键；返回最后一帧的上报时间
+    pub fn schedule_macro(&mut self, start: Duration, steps: &[MacroStep]) -> Duration {
+        let mut time = start;
+        let mut held: Vec<EV_KEY> = vec![];
+        for step in steps {
+            time = match step {
+                MacroStep::Combo(codes) => {
+                    let (modifiers, keys): (Vec<_>, Vec<_>) = codes
+                        .iter()
+                        .cloned()
+                        .partition(|code| binding::is_modifier(*code));
+                    let frames = [
+                        modifiers.iter().map(|code| (*code, 1)).collect(),
+                        keys.iter().map(|code| (*code, 1)).collect(),
+                        keys.iter().rev().map(|code| (*code, 0)).collect(),
+                        modifiers.iter().rev().map(|code| (*code, 0)).collect(),
+                    ];
+                    self.schedule(time, frames, Duration::ZERO)
+                }
+                MacroStep::Delay(delay) => time + *delay,
+                MacroStep::Down(codes) => {
+                    held.retain(|code| !codes.contains(code));
+                    held.extend(codes.iter().cloned());
+                    let frame = codes.iter().map(|code| (*code, 1)).collect();
+                    self.schedule(time, [frame], Duration::ZERO)
+                }
+                MacroStep::Up(codes) => {
+                    held.retain(|code| !codes.contains(code));
+                    let frame = codes.iter().map(|code| (*code, 0)).collect();
+                    self.schedule(time, [frame], Duration::ZERO)
+                }
+            };
+        }
+        let frame = held.iter().rev().map(|code| (*code, 0)).collect();
+        self.schedule(time, [frame], Duration::ZERO)
+    }
+
+    // 下一帧的上报时间
+    pub fn next_time(&self) -> Option<Duration> {
+        self.keys.front().map(|key| key.time)
+    }
+
+    // 取出下一帧到now为止应当上报的事件，now为None时不论时间；没有这样的帧时返回None
+    //
+    // is_held在上报时判断按键是否仍被其他来源按住：这样的键不释放，除非队列中之后还会再次按下它
+    pub fn pop_frame<F>(&mut self, now: Option<Duration>, is_held: F) -> Option<Vec<(EV_KEY, i32)>>
+    where
+        F: Fn(EV_KEY) -> bool,
+    {
+        let first = self.keys.front()?;
+        if now.is_some_and(|now| first.time > now) {
+            return None;
+        }
+        let frame = first.frame;
+        let mut events = vec![];
+        while let Some(key) = self.keys.front() {
+            if key.frame != frame {
+                break;
+            }
+            let key = self.keys.pop_front().unwrap();
+            if key.value == 0 && is_held(key.code) && !self.will_press(key.code) {
+                continue;
+            }
+            events.push((key.code, key.value));
+        }
+        Some(events)
+    }
+
+    fn will_press(&self, code: EV_KEY) -> bool {
+        self.keys
+            .iter()
+            .any(|key| key.code == code && key.value == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn drain(queue: &mut KeyQueue, is_held: impl Fn(EV_KEY) -> bool) -> Vec<Vec<(EV_KEY, i32)>> {
+        let mut frames = vec![];
+        while let Some(frame) = queue.pop_frame(None, &is_held) {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[test]
+    fn macro_keeps_latched_modifier() {
+        let mut queue = KeyQueue::default();
+        let steps = [
+            MacroStep::Combo(vec![EV_KEY::KEY_LEFTCTRL, EV_KEY::KEY_C]),
+            MacroStep::Up(vec![EV_KEY::KEY_LEFTCTRL]),
+        ];
+        queue.schedule_macro(Duration::ZERO, &steps);
+        let frames = drain(&mut queue, |code| code == EV_KEY::KEY_LEFTCTRL);
+        assert_eq!(
+            frames,
+            [
+                vec![(EV_KEY::KEY_LEFTCTRL, 1)],
+                vec![(EV_KEY::KEY_C, 1)],
+                vec![(EV_KEY::KEY_C, 0)],
+                vec![],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn held_state_is_checked_when_emitting() {
+        let mut queue = KeyQueue::default();
+        let steps = [
+            MacroStep::Down(vec![EV_KEY::KEY_LEFTSHIFT]),
+            MacroStep::Delay(Duration::from_millis(100)),
+        ];
+        let end = queue.schedule_macro(Duration::ZERO, &steps);
+        assert_eq!(end, Duration::from_millis(100));
+        let latched = Cell::new(false);
+        let is_held = |code| latched.get() && code == EV_KEY::KEY_LEFTSHIFT;
+        assert_eq!(
+            queue.pop_frame(Some(Duration::ZERO), is_held),
+            Some(vec![(EV_KEY::KEY_LEFTSHIFT, 1)])
+        );
+        assert_eq!(queue.pop_frame(Some(Duration::ZERO), is_held), None);
+        // 宏执行期间锁定了shift，宏结束时不释放
+        latched.set(true);
+        assert_eq!(queue.pop_frame(Some(end), is_held), Some(vec![]));
+        assert_eq!(queue.next_time(), None);
+    }
+
+    #[test]
+    fn release_before_later_press_is_kept() {
+        let mut queue = KeyQueue::default();
+        queue.schedule(
+            Duration::ZERO,
+            [vec![(EV_KEY::KEY_A, 1)], vec![(EV_KEY::KEY_A, 0)]],
+            Duration::ZERO,
+        );
+        queue.schedule(Duration::ZERO, [vec![(EV_KEY::KEY_A, 1)]], Duration::ZERO);
+        let frames = drain(&mut queue, |code| code == EV_KEY::KEY_A);
+        assert_eq!(
+            frames,
+            [
+                vec![(EV_KEY::KEY_A, 1)],
+                vec![(EV_KEY::KEY_A, 0)],
+                vec![(EV_KEY::KEY_A, 1)]
+            ]
+        );
+    }
+
+    #[test]
+    fn frames_are_spaced_by_delay() {
+        let mut queue = KeyQueue::default();
+        let delay = Duration::from_millis(20);
+        let frames = [1, 0].map(|value| vec![(EV_KEY::BTN_LEFT, value)]);
+        assert_eq!(queue.schedule(Duration::ZERO, frames, delay), delay);
+        // 新的事件排在已有的事件之后
+        assert_eq!(
+            queue.schedule(Duration::ZERO, [vec![(EV_KEY::KEY_A, 1)]], delay),
+            delay
+        );
+        assert!(queue.pop_frame(Some(Duration::ZERO), |_| false).is_some());
+        assert!(queue.pop_frame(Some(Duration::ZERO), |_| false).is_none());
+        assert_eq!(drain(&mut queue, |_| false).len(), 2);
+    }
+}
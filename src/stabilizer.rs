@@ -0,0 +1,82 @@
+// 笔画稳定器（“懒刷”）：虚拟笔尖通过一根长度固定的虚拟绳子被真实笔尖拖着走，
+// 只有当真实笔尖离虚拟笔尖的距离超过绳长时，虚拟笔尖才会沿两者连线向真实笔尖移动
+pub struct Stabilizer {
+    length: f32,
+    position: Option<(f32, f32)>,
+}
+impl Stabilizer {
+    pub fn new(length: f32) -> Self {
+        Self {
+            length,
+            position: None,
+        }
+    }
+
+    pub fn set_length(&mut self, length: f32) {
+        self.length = length;
+    }
+
+    // 清空虚拟笔尖的位置，下一个坐标将原样输出
+    pub fn reset(&mut self) {
+        self.position = None;
+    }
+
+    // 虚拟笔尖是否落后于真实笔尖
+    pub fn lagging(&self, x: u16, y: u16) -> bool {
+        match self.position {
+            Some((px, py)) => px.round() as u16 != x || py.round() as u16 != y,
+            None => false,
+        }
+    }
+
+    pub fn update(&mut self, x: u16, y: u16) -> (u16, u16) {
+        let (x, y) = (x as f32, y as f32);
+        let (px, py) = match self.position {
+            Some(position) => position,
+            None => {
+                self.position = Some((x, y));
+                return (x as u16, y as u16);
+            }
+        };
+        let (dx, dy) = (x - px, y - py);
+        let distance = (dx * dx + dy * dy).sqrt();
+        if distance > self.length {
+            let ratio = (distance - self.length) / distance;
+            self.position = Some((px + dx * ratio, py + dy * ratio));
+        }
+        let (px, py) = self.position.unwrap();
+        (px.round() as u16, py.round() as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_position_is_unchanged() {
+        let mut stabilizer = Stabilizer::new(10f32);
+        assert_eq!(stabilizer.update(100, 200), (100, 200));
+        assert!(!stabilizer.lagging(100, 200));
+    }
+
+    #[test]
+    fn moves_only_beyond_length() {
+        let mut stabilizer = Stabilizer::new(10f32);
+        stabilizer.update(100, 100);
+        assert_eq!(stabilizer.update(108, 100), (100, 100));
+        assert!(stabilizer.lagging(108, 100));
+        assert_eq!(stabilizer.update(130, 100), (120, 100));
+        assert_eq!(stabilizer.update(120, 100), (120, 100));
+    }
+
+    #[test]
+    fn reset_and_set_length() {
+        let mut stabilizer = Stabilizer::new(10f32);
+        stabilizer.update(100, 100);
+        stabilizer.reset();
+        assert_eq!(stabilizer.update(500, 500), (500, 500));
+        stabilizer.set_length(0f32);
+        assert_eq!(stabilizer.update(510, 500), (510, 500));
+    }
+}
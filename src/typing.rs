@@ -0,0 +1,124 @@
+use anyhow::{Result, anyhow};
+use evdev_rs::enums::EV_KEY;
+
+use crate::config::{MacroStep, UnicodeInputConfig};
+
+// 把文本转换为依次输入每个字符的宏步骤；ASCII字符按美式键盘布局输入，其他字符通过Unicode输入法输入
+pub fn text_steps(text: &str, unicode_input: &UnicodeInputConfig) -> Result<Vec<MacroStep>> {
+    let mut steps = vec![];
+    for c in text.chars() {
+        if let Some((code, shift)) = ascii_key(c) {
+            steps.push(MacroStep::Combo(match shift {
+                true => vec![EV_KEY::KEY_LEFTSHIFT, code],
+                false => vec![code],
+            }));
+            continue;
+        }
+        if c.is_control() {
+            return Err(anyhow!("无法输入控制字符{:?}", c));
+        }
+        match unicode_input {
+            UnicodeInputConfig::Hex { start, end } => {
+                steps.push(MacroStep::Combo(start.clone()));
+                for digit in format!("{:x}", c as u32).chars() {
+                    let (code, _) = ascii_key(digit).unwrap();
+                    steps.push(MacroStep::Combo(vec![code]));
+                }
+                steps.push(MacroStep::Combo(end.clone()));
+            }
+            UnicodeInputConfig::None => {
+                return Err(anyhow!("字符'{}'无法直接输入，需要配置unicodeInput", c));
+            }
+        }
+    }
+    if steps.is_empty() {
+        return Err(anyhow!("要输入的文本为空"));
+    }
+    Ok(steps)
+}
+
+// 美式键盘布局下输入ASCII字符的按键码，以及是否需要按住shift
+#[rustfmt::skip]
+fn ascii_key(c: char) -> Option<(EV_KEY, bool)> {
+    let lower = c.to_ascii_lowercase();
+    if lower.is_ascii_lowercase() {
+        let code = crate::binding::key_code(&lower.to_string())?;
+        return Some((code, c.is_ascii_uppercase()));
+    }
+    let key = match c {
+        '0' => (EV_KEY::KEY_0, false), '1' => (EV_KEY::KEY_1, false), '2' => (EV_KEY::KEY_2, false),
+        '3' => (EV_KEY::KEY_3, false), '4' => (EV_KEY::KEY_4, false), '5' => (EV_KEY::KEY_5, false),
+        '6' => (EV_KEY::KEY_6, false), '7' => (EV_KEY::KEY_7, false), '8' => (EV_KEY::KEY_8, false),
+        '9' => (EV_KEY::KEY_9, false),
+        ')' => (EV_KEY::KEY_0, true), '!' => (EV_KEY::KEY_1, true), '@' => (EV_KEY::KEY_2, true),
+        '#' => (EV_KEY::KEY_3, true), '$' => (EV_KEY::KEY_4, true), '%' => (EV_KEY::KEY_5, true),
+        '^' => (EV_KEY::KEY_6, true), '&' => (EV_KEY::KEY_7, true), '*' => (EV_KEY::KEY_8, true),
+        '(' => (EV_KEY::KEY_9, true),
+        '-' => (EV_KEY::KEY_MINUS, false), '_' => (EV_KEY::KEY_MINUS, true),
+        '=' => (EV_KEY::KEY_EQUAL, false), '+' => (EV_KEY::KEY_EQUAL, true),
+        '[' => (EV_KEY::KEY_LEFTBRACE, false), '{' => (EV_KEY::KEY_LEFTBRACE, true),
+        ']' => (EV_KEY::KEY_RIGHTBRACE, false), '}' => (EV_KEY::KEY_RIGHTBRACE, true),
+        '\\' => (EV_KEY::KEY_BACKSLASH, false), '|' => (EV_KEY::KEY_BACKSLASH, true),
+        ';' => (EV_KEY::KEY_SEMICOLON, false), ':' => (EV_KEY::KEY_SEMICOLON, true),
+        '\'' => (EV_KEY::KEY_APOSTROPHE, false), '"' => (EV_KEY::KEY_APOSTROPHE, true),
+        ',' => (EV_KEY::KEY_COMMA, false), '<' => (EV_KEY::KEY_COMMA, true),
+        '.' => (EV_KEY::KEY_DOT, false), '>' => (EV_KEY::KEY_DOT, true),
+        '/' => (EV_KEY::KEY_SLASH, false), '?' => (EV_KEY::KEY_SLASH, true),
+        '`' => (EV_KEY::KEY_GRAVE, false), '~' => (EV_KEY::KEY_GRAVE, true),
+        ' ' => (EV_KEY::KEY_SPACE, false), '\t' => (EV_KEY::KEY_TAB, false),
+        '\n' => (EV_KEY::KEY_ENTER, false),
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex() -> UnicodeInputConfig {
+        UnicodeInputConfig::Hex {
+            start: vec![EV_KEY::KEY_LEFTCTRL, EV_KEY::KEY_LEFTSHIFT, EV_KEY::KEY_U],
+            end: vec![EV_KEY::KEY_SPACE],
+        }
+    }
+
+    #[test]
+    fn ascii_text() {
+        let steps = text_steps("aB+\n", &UnicodeInputConfig::None).unwrap();
+        assert_eq!(
+            steps,
+            [
+                MacroStep::Combo(vec![EV_KEY::KEY_A]),
+                MacroStep::Combo(vec![EV_KEY::KEY_LEFTSHIFT, EV_KEY::KEY_B]),
+                MacroStep::Combo(vec![EV_KEY::KEY_LEFTSHIFT, EV_KEY::KEY_EQUAL]),
+                MacroStep::Combo(vec![EV_KEY::KEY_ENTER]),
+            ]
+        );
+    }
+
+    #[test]
+    fn unicode_text() {
+        let steps = text_steps("é", &hex()).unwrap();
+        assert_eq!(
+            steps,
+            [
+                MacroStep::Combo(vec![
+                    EV_KEY::KEY_LEFTCTRL,
+                    EV_KEY::KEY_LEFTSHIFT,
+                    EV_KEY::KEY_U
+                ]),
+                MacroStep::Combo(vec![EV_KEY::KEY_E]),
+                MacroStep::Combo(vec![EV_KEY::KEY_9]),
+                MacroStep::Combo(vec![EV_KEY::KEY_SPACE]),
+            ]
+        );
+    }
+
+    #[test]
+    fn text_errors() {
+        assert!(text_steps("", &hex()).is_err());
+        assert!(text_steps("é", &UnicodeInputConfig::None).is_err());
+        assert!(text_steps("\u{7}", &hex()).is_err());
+    }
+}